@@ -1,6 +1,6 @@
 pub mod token;
 
-use token::{Token, TokenType};
+use token::{TemplatePart, Token, TokenType};
 
 pub struct Lexer {
     input: Vec<char>,
@@ -13,7 +13,7 @@ pub struct Lexer {
 impl Lexer {
     pub fn new(input: String) -> Self {
         let chars: Vec<char> = input.chars().collect();
-        let current_char = chars.get(0).copied();
+        let current_char = chars.first().copied();
         Lexer {
             input: chars,
             position: 0,
@@ -67,50 +67,277 @@ impl Lexer {
         }
     }
 
-    fn read_string(&mut self) -> String {
+    fn read_string(&mut self) -> Result<TokenType, String> {
+        let (start_line, start_column) = (self.line, self.column);
+        let mut parts = Vec::new();
         let mut result = String::new();
         self.advance(); // Skip opening quote
 
-        while let Some(ch) = self.current_char {
-            if ch == '"' {
-                self.advance(); // Skip closing quote
-                break;
-            } else if ch == '\\' {
-                self.advance();
-                match self.current_char {
-                    Some('n') => result.push('\n'),
-                    Some('t') => result.push('\t'),
-                    Some('r') => result.push('\r'),
-                    Some('\\') => result.push('\\'),
-                    Some('"') => result.push('"'),
-                    _ => result.push('\\'),
+        loop {
+            match self.current_char {
+                None => {
+                    return Err(format!("Unterminated string starting at {}:{}", start_line, start_column));
+                }
+                Some('"') => {
+                    self.advance(); // Skip closing quote
+                    break;
+                }
+                Some('\\') => {
+                    let (line, column) = (self.line, self.column);
+                    self.advance();
+                    let escaped = match self.current_char {
+                        Some('x') => self.read_hex_escape(line, column)?,
+                        Some('u') => self.read_unicode_escape(line, column)?,
+                        other => {
+                            self.advance();
+                            match other {
+                                Some('n') => '\n',
+                                Some('t') => '\t',
+                                Some('r') => '\r',
+                                Some('0') => '\0',
+                                Some('\\') => '\\',
+                                Some('"') => '"',
+                                Some('$') => '$',
+                                _ => '\\',
+                            }
+                        }
+                    };
+                    result.push(escaped);
+                }
+                Some('$') if self.peek(1) == Some('{') => {
+                    self.advance();
+                    self.advance();
+                    if !result.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut result)));
+                    }
+                    parts.push(TemplatePart::Code(self.read_interpolation()?));
+                }
+                Some(ch) => {
+                    result.push(ch);
+                    self.advance();
                 }
-                self.advance();
-            } else {
-                result.push(ch);
-                self.advance();
             }
         }
-        result
+
+        if parts.is_empty() {
+            return Ok(TokenType::String(result));
+        }
+        if !result.is_empty() {
+            parts.push(TemplatePart::Literal(result));
+        }
+        Ok(TokenType::TemplateString(parts))
+    }
+
+    /// Read a `\xNN` escape (an ASCII character given as two hex digits); the
+    /// backslash has already been consumed
+    fn read_hex_escape(&mut self, line: usize, column: usize) -> Result<char, String> {
+        self.advance(); // Skip 'x'
+        let mut digits = String::new();
+        for _ in 0..2 {
+            match self.current_char {
+                Some(ch) if ch.is_ascii_hexdigit() => {
+                    digits.push(ch);
+                    self.advance();
+                }
+                _ => return Err(format!("Invalid \\x escape at {}:{}: expected two hex digits", line, column)),
+            }
+        }
+        let code = u8::from_str_radix(&digits, 16).unwrap();
+        if !code.is_ascii() {
+            return Err(format!("Invalid \\x escape at {}:{}: \\x{} is outside the ASCII range", line, column, digits));
+        }
+        Ok(code as char)
+    }
+
+    /// Read a `\u{...}` escape holding a hex unicode code point; the backslash has
+    /// already been consumed
+    fn read_unicode_escape(&mut self, line: usize, column: usize) -> Result<char, String> {
+        self.advance(); // Skip 'u'
+        if self.current_char != Some('{') {
+            return Err(format!("Invalid unicode escape at {}:{}: expected '{{'", line, column));
+        }
+        self.advance();
+
+        let mut digits = String::new();
+        while let Some(ch) = self.current_char.filter(|ch| ch.is_ascii_hexdigit()) {
+            digits.push(ch);
+            self.advance();
+        }
+        if self.current_char != Some('}') || digits.is_empty() || digits.len() > 6 {
+            return Err(format!("Invalid unicode escape at {}:{}: expected 1 to 6 hex digits and '}}'", line, column));
+        }
+        self.advance();
+
+        let code = u32::from_str_radix(&digits, 16).unwrap();
+        char::from_u32(code)
+            .ok_or_else(|| format!("Invalid unicode code point U+{:X} at {}:{}", code, line, column))
     }
 
-    fn read_number(&mut self) -> String {
+    /// Read the source of a `${...}` interpolation up to its closing brace; the
+    /// opening `${` has already been consumed
+    fn read_interpolation(&mut self) -> Result<String, String> {
+        let (start_line, start_column) = (self.line, self.column);
+        let mut code = String::new();
+        let mut depth = 0;
+
+        loop {
+            match self.current_char {
+                None => {
+                    return Err(format!("Unterminated interpolation starting at {}:{}", start_line, start_column));
+                }
+                Some('}') if depth == 0 => {
+                    self.advance();
+                    return Ok(code);
+                }
+                Some('"') => self.copy_string_source(&mut code)?,
+                Some(ch) => {
+                    if ch == '{' {
+                        depth += 1;
+                    } else if ch == '}' {
+                        depth -= 1;
+                    }
+                    code.push(ch);
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    /// Copy a string literal nested inside an interpolation verbatim, so it can
+    /// contain braces and interpolations of its own
+    fn copy_string_source(&mut self, code: &mut String) -> Result<(), String> {
+        let (start_line, start_column) = (self.line, self.column);
+        code.push('"');
+        self.advance();
+
+        loop {
+            match self.current_char {
+                None => {
+                    return Err(format!("Unterminated string starting at {}:{}", start_line, start_column));
+                }
+                Some('"') => {
+                    code.push('"');
+                    self.advance();
+                    return Ok(());
+                }
+                Some('\\') => {
+                    code.push('\\');
+                    self.advance();
+                    if let Some(ch) = self.current_char {
+                        code.push(ch);
+                        self.advance();
+                    }
+                }
+                Some('$') if self.peek(1) == Some('{') => {
+                    self.advance();
+                    self.advance();
+                    code.push_str("${");
+                    code.push_str(&self.read_interpolation()?);
+                    code.push('}');
+                }
+                Some(ch) => {
+                    code.push(ch);
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    fn read_number(&mut self) -> Result<String, String> {
+        if self.current_char == Some('0') {
+            let radix = match self.peek(1) {
+                Some('x') | Some('X') => Some(16),
+                Some('o') | Some('O') => Some(8),
+                Some('b') | Some('B') => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                return self.read_radix_number(radix);
+            }
+        }
+
         let mut result = String::new();
         let mut has_dot = false;
+        let mut has_exponent = false;
 
         while let Some(ch) = self.current_char {
             if ch.is_ascii_digit() {
                 result.push(ch);
                 self.advance();
-            } else if ch == '.' && !has_dot && self.peek(1).map_or(false, |c| c.is_ascii_digit()) {
+            } else if ch == '_' {
+                self.read_separator(&mut result, 10)?;
+            } else if ch == '.' && !has_dot && !has_exponent && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
                 has_dot = true;
                 result.push(ch);
                 self.advance();
+            } else if (ch == 'e' || ch == 'E') && !has_exponent && self.exponent_follows() {
+                has_exponent = true;
+                result.push(ch);
+                self.advance();
+                if let Some(sign @ ('+' | '-')) = self.current_char {
+                    result.push(sign);
+                    self.advance();
+                }
             } else {
                 break;
             }
         }
-        result
+        Ok(result)
+    }
+
+    /// Whether the `e`/`E` at the current position starts an exponent (`e3`, `e-3`, `E+3`)
+    fn exponent_follows(&self) -> bool {
+        match self.peek(1) {
+            Some('+') | Some('-') => self.peek(2).is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    /// Consume a `_` digit separator, which must sit between two digits
+    fn read_separator(&mut self, result: &mut String, radix: u32) -> Result<(), String> {
+        let after_digit = result.chars().last().is_some_and(|c| c.is_digit(radix));
+        let before_digit = self.peek(1).is_some_and(|c| c.is_digit(radix));
+        if !after_digit || !before_digit {
+            return Err(format!("Invalid '_' separator in number literal at {}:{}", self.line, self.column));
+        }
+        result.push('_');
+        self.advance();
+        Ok(())
+    }
+
+    /// Read a `0x`/`0o`/`0b` literal, keeping the prefix for the parser to decode
+    fn read_radix_number(&mut self, radix: u32) -> Result<String, String> {
+        let mut result = String::new();
+        for _ in 0..2 {
+            if let Some(ch) = self.current_char {
+                result.push(ch);
+                self.advance();
+            }
+        }
+
+        let mut has_digits = false;
+        while let Some(ch) = self.current_char {
+            if ch.is_digit(radix) {
+                has_digits = true;
+                result.push(ch);
+                self.advance();
+            } else if ch == '_' {
+                self.read_separator(&mut result, radix)?;
+            } else if ch.is_alphanumeric() {
+                return Err(format!(
+                    "Invalid digit '{}' in base {} literal at {}:{}",
+                    ch, radix, self.line, self.column
+                ));
+            } else {
+                break;
+            }
+        }
+
+        if !has_digits {
+            return Err(format!("Expected digits after '{}' at {}:{}", result, self.line, self.column));
+        }
+        Ok(result)
     }
 
     fn read_identifier(&mut self) -> String {
@@ -130,6 +357,7 @@ impl Lexer {
         let id = self.read_identifier();
         match id.as_str() {
             "func" => TokenType::Func,
+            "let" => TokenType::Let,
             "return" => TokenType::Return,
             "match" => TokenType::Match,
             "case" => TokenType::Case,
@@ -139,11 +367,18 @@ impl Lexer {
             "if" => TokenType::If,
             "else" => TokenType::Else,
             "while" => TokenType::While,
+            "repeat" => TokenType::Repeat,
             "for" => TokenType::For,
             "in" => TokenType::In,
             "class" => TokenType::Class,
             "extends" => TokenType::Extends,
             "new" => TokenType::New,
+            "override" => TokenType::Override,
+            "const" => TokenType::Const,
+            "public" => TokenType::Public,
+            "private" => TokenType::Private,
+            "super" => TokenType::Super,
+            "is" => TokenType::Is,
             _ => TokenType::Identifier(id),
         }
     }
@@ -173,11 +408,10 @@ impl Lexer {
                     if ch.is_alphabetic() || ch == '_' {
                         self.identifier_or_keyword()
                     } else if ch.is_ascii_digit() {
-                        let num = self.read_number();
+                        let num = self.read_number()?;
                         TokenType::Number(num)
                     } else if ch == '"' {
-                        let s = self.read_string();
-                        TokenType::String(s)
+                        self.read_string()?
                     } else {
                         match ch {
                             '=' => {
@@ -194,19 +428,42 @@ impl Lexer {
                             }
                             '+' => {
                                 self.advance();
-                                TokenType::Plus
+                                if self.current_char == Some('=') {
+                                    self.advance();
+                                    TokenType::PlusAssign
+                                } else {
+                                    TokenType::Plus
+                                }
                             }
                             '-' => {
                                 self.advance();
-                                TokenType::Minus
+                                if self.current_char == Some('=') {
+                                    self.advance();
+                                    TokenType::MinusAssign
+                                } else {
+                                    TokenType::Minus
+                                }
                             }
                             '*' => {
                                 self.advance();
-                                TokenType::Star
+                                if self.current_char == Some('=') {
+                                    self.advance();
+                                    TokenType::StarAssign
+                                } else if self.current_char == Some('*') {
+                                    self.advance();
+                                    TokenType::StarStar
+                                } else {
+                                    TokenType::Star
+                                }
                             }
                             '/' => {
                                 self.advance();
-                                TokenType::Slash
+                                if self.current_char == Some('=') {
+                                    self.advance();
+                                    TokenType::SlashAssign
+                                } else {
+                                    TokenType::Slash
+                                }
                             }
                             '!' => {
                                 self.advance();
@@ -222,6 +479,9 @@ impl Lexer {
                                 if self.current_char == Some('=') {
                                     self.advance();
                                     TokenType::LessEqual
+                                } else if self.current_char == Some('<') {
+                                    self.advance();
+                                    TokenType::ShiftLeft
                                 } else {
                                     TokenType::Less
                                 }
@@ -231,6 +491,9 @@ impl Lexer {
                                 if self.current_char == Some('=') {
                                     self.advance();
                                     TokenType::GreaterEqual
+                                } else if self.current_char == Some('>') {
+                                    self.advance();
+                                    TokenType::ShiftRight
                                 } else {
                                     TokenType::Greater
                                 }
@@ -241,7 +504,7 @@ impl Lexer {
                                     self.advance();
                                     TokenType::And
                                 } else {
-                                    return Err(format!("Unexpected character '&' at {}:{}", token_line, token_column));
+                                    TokenType::Ampersand
                                 }
                             }
                             '|' => {
@@ -250,7 +513,27 @@ impl Lexer {
                                     self.advance();
                                     TokenType::Or
                                 } else {
-                                    return Err(format!("Unexpected character '|' at {}:{}", token_line, token_column));
+                                    TokenType::Pipe
+                                }
+                            }
+                            '^' => {
+                                self.advance();
+                                TokenType::Caret
+                            }
+                            '~' => {
+                                self.advance();
+                                TokenType::Tilde
+                            }
+                            '?' => {
+                                self.advance();
+                                if self.current_char == Some('?') {
+                                    self.advance();
+                                    TokenType::QuestionQuestion
+                                } else if self.current_char == Some('.') {
+                                    self.advance();
+                                    TokenType::QuestionDot
+                                } else {
+                                    return Err(format!("Unexpected character '?' at {}:{}", token_line, token_column));
                                 }
                             }
                             '(' => {
@@ -291,7 +574,13 @@ impl Lexer {
                             }
                             '.' => {
                                 self.advance();
-                                TokenType::Dot
+                                if self.current_char == Some('.') && self.peek(1) == Some('.') {
+                                    self.advance();
+                                    self.advance();
+                                    TokenType::Ellipsis
+                                } else {
+                                    TokenType::Dot
+                                }
                             }
                             _ => {
                                 return Err(format!("Unexpected character '{}' at {}:{}", ch, token_line, token_column));
@@ -314,13 +603,36 @@ mod tests {
 
     #[test]
     fn test_simple_tokens() {
-        let mut lexer = Lexer::new("= + - * /".to_string());
+        let mut lexer = Lexer::new("= + - * / **".to_string());
         let tokens = lexer.tokenize().unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Assign);
         assert_eq!(tokens[1].token_type, TokenType::Plus);
         assert_eq!(tokens[2].token_type, TokenType::Minus);
         assert_eq!(tokens[3].token_type, TokenType::Star);
         assert_eq!(tokens[4].token_type, TokenType::Slash);
+        assert_eq!(tokens[5].token_type, TokenType::StarStar);
+    }
+
+    #[test]
+    fn test_bitwise_tokens() {
+        let mut lexer = Lexer::new("& | ^ ~ << >> && || <= >=".to_string());
+        let types: Vec<TokenType> = lexer.tokenize().unwrap().into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Ampersand,
+                TokenType::Pipe,
+                TokenType::Caret,
+                TokenType::Tilde,
+                TokenType::ShiftLeft,
+                TokenType::ShiftRight,
+                TokenType::And,
+                TokenType::Or,
+                TokenType::LessEqual,
+                TokenType::GreaterEqual,
+                TokenType::Eof,
+            ]
+        );
     }
 
     #[test]
@@ -354,4 +666,99 @@ mod tests {
         assert!(matches!(tokens[0].token_type, TokenType::Number(_)));
         assert!(matches!(tokens[1].token_type, TokenType::Number(_)));
     }
+
+    #[test]
+    fn test_radix_number_literals() {
+        let mut lexer = Lexer::new("0xFF 0o17 0b1010 0".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::Number("0xFF".to_string()));
+        assert_eq!(tokens[1].token_type, TokenType::Number("0o17".to_string()));
+        assert_eq!(tokens[2].token_type, TokenType::Number("0b1010".to_string()));
+        assert_eq!(tokens[3].token_type, TokenType::Number("0".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_radix_digit() {
+        let mut lexer = Lexer::new("x = 0b102".to_string());
+        let err = lexer.tokenize().unwrap_err();
+        assert_eq!(err, "Invalid digit '2' in base 2 literal at 1:9");
+
+        let mut lexer = Lexer::new("0x".to_string());
+        assert!(lexer.tokenize().is_err());
+    }
+
+    #[test]
+    fn test_exponent_and_separator_literals() {
+        let mut lexer = Lexer::new("1e3 1.5E-2 2e+4 1_000 0xff_ff".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::Number("1e3".to_string()));
+        assert_eq!(tokens[1].token_type, TokenType::Number("1.5E-2".to_string()));
+        assert_eq!(tokens[2].token_type, TokenType::Number("2e+4".to_string()));
+        assert_eq!(tokens[3].token_type, TokenType::Number("1_000".to_string()));
+        assert_eq!(tokens[4].token_type, TokenType::Number("0xff_ff".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_separators() {
+        let mut lexer = Lexer::new("1__0".to_string());
+        assert_eq!(lexer.tokenize().unwrap_err(), "Invalid '_' separator in number literal at 1:2");
+
+        for source in ["1_", "1_.5", "0x_ff", "0b1__0"] {
+            let mut lexer = Lexer::new(source.to_string());
+            assert!(lexer.tokenize().is_err(), "{} should not lex", source);
+        }
+    }
+
+    #[test]
+    fn test_template_string() {
+        let mut lexer = Lexer::new("\"sum=${a+b}!\" \"plain\"".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(
+            tokens[0].token_type,
+            TokenType::TemplateString(vec![
+                TemplatePart::Literal("sum=".to_string()),
+                TemplatePart::Code("a+b".to_string()),
+                TemplatePart::Literal("!".to_string()),
+            ])
+        );
+        assert_eq!(tokens[1].token_type, TokenType::String("plain".to_string()));
+    }
+
+    #[test]
+    fn test_nested_and_escaped_interpolation() {
+        let mut lexer = Lexer::new(r#""a ${"b ${c}"} ${ {k: 1} } \${d}""#.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(
+            tokens[0].token_type,
+            TokenType::TemplateString(vec![
+                TemplatePart::Literal("a ".to_string()),
+                TemplatePart::Code(r#""b ${c}""#.to_string()),
+                TemplatePart::Literal(" ".to_string()),
+                TemplatePart::Code(" {k: 1} ".to_string()),
+                TemplatePart::Literal(" ${d}".to_string()),
+            ])
+        );
+
+        let mut lexer = Lexer::new("\"${a\"".to_string());
+        assert!(lexer.tokenize().is_err());
+    }
+
+    #[test]
+    fn test_unicode_and_byte_escapes() {
+        let mut lexer = Lexer::new(r#""\u{41}\u{1F600}\x41\x7e\0""#.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::String("A\u{1F600}A~\0".to_string()));
+
+        for (source, message) in [
+            (r#""\u{110000}""#, "Invalid unicode code point U+110000 at 1:2"),
+            (r#""\u{D800}""#, "Invalid unicode code point U+D800 at 1:2"),
+            (r#""\u41""#, "Invalid unicode escape at 1:2: expected '{'"),
+            (r#""ab\u{}""#, "Invalid unicode escape at 1:4: expected 1 to 6 hex digits and '}'"),
+            (r#""\x4""#, "Invalid \\x escape at 1:2: expected two hex digits"),
+            (r#""\xff""#, "Invalid \\x escape at 1:2: \\xff is outside the ASCII range"),
+        ] {
+            let mut lexer = Lexer::new(source.to_string());
+            assert_eq!(lexer.tokenize().unwrap_err(), message, "{}", source);
+        }
+    }
 }
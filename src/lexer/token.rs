@@ -3,6 +3,7 @@ pub enum TokenType {
     // Literals
     Number(String),
     String(String),
+    TemplateString(Vec<TemplatePart>), // string containing `${expr}` interpolations
     Identifier(String),
     True,
     False,
@@ -10,23 +11,32 @@ pub enum TokenType {
 
     // Keywords
     Func,
+    Let,
     Return,
     Match,
     Case,
     If,
     Else,
     While,
+    Repeat,
     For,
     In,
     Class,
     Extends,
     New,
+    Override,
+    Const,
+    Public,
+    Private,
+    Super,
+    Is,
 
     // Operators
     Assign,       // =
     Plus,         // +
     Minus,        // -
     Star,         // *
+    StarStar,     // **
     Slash,        // /
     Bang,         // !
     EqualEqual,   // ==
@@ -37,7 +47,19 @@ pub enum TokenType {
     GreaterEqual, // >=
     And,          // &&
     Or,           // ||
+    QuestionQuestion, // ??
+    QuestionDot,  // ?.
+    Ampersand,    // &
+    Pipe,         // |
+    Caret,        // ^
+    Tilde,        // ~
+    ShiftLeft,    // <<
+    ShiftRight,   // >>
     Arrow,        // =>
+    PlusAssign,   // +=
+    MinusAssign,  // -=
+    StarAssign,   // *=
+    SlashAssign,  // /=
 
     // Delimiters
     LeftParen,    // (
@@ -50,11 +72,18 @@ pub enum TokenType {
     Colon,        // :
     Semicolon,    // ;
     Dot,          // .
+    Ellipsis,     // ...
 
     // Special
     Eof,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart {
+    Literal(String),
+    Code(String), // source of an interpolated expression, parsed by the parser
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
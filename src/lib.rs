@@ -0,0 +1,21 @@
+//! The Platypus language: lexer, parser, and tree-walking interpreter.
+//!
+//! Embedders usually only need [`Interpreter`]; [`Lexer`] and [`Parser`] are
+//! available for tools that work with tokens or the syntax tree:
+//!
+//! ```
+//! let mut interpreter = platypus::Interpreter::new();
+//! interpreter.run("func double(n) { return n * 2 }").unwrap();
+//! assert_eq!(interpreter.eval("double(21)").unwrap(), platypus::Value::Number(42.0));
+//! ```
+
+pub mod lexer;
+pub mod optimize;
+pub mod parser;
+pub mod resolver;
+pub mod runtime;
+
+pub use lexer::Lexer;
+pub use parser::Parser;
+pub use runtime::value::Value;
+pub use runtime::{Interpreter, NativeFn, RuntimeError};
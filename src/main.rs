@@ -1,15 +1,13 @@
-mod lexer;
-mod parser;
-mod runtime;
-
 use std::env;
 use std::fs;
 use std::io::{self, Write};
 use std::process;
 
-use lexer::Lexer;
-use parser::Parser;
-use runtime::Interpreter;
+use platypus::lexer::token::TokenType;
+use platypus::lexer::Lexer;
+use platypus::optimize;
+use platypus::parser::{self, Parser};
+use platypus::runtime::{self, Interpreter, RuntimeError};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -21,16 +19,45 @@ fn main() {
 
     match args[1].as_str() {
         "run" => {
-            if args.len() < 3 {
-                eprintln!("Error: No input file provided");
-                print_usage();
-                process::exit(1);
+            let mut options = RunOptions::default();
+            let mut filename = None;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--trace" => options.trace = true,
+                    "--profile" => options.profile = true,
+                    "--ast" => options.ast = true,
+                    "--tokens" => options.tokens = true,
+                    "--check" => options.check = true,
+                    "--optimize" => options.optimize = true,
+                    _ if arg.starts_with("--") => {
+                        eprintln!("Error: Unknown option '{}'", arg);
+                        print_usage();
+                        process::exit(1);
+                    }
+                    _ => filename = Some(arg.clone()),
+                }
+            }
+
+            match filename {
+                Some(filename) => run_file(&filename, &options),
+                None => {
+                    eprintln!("Error: No input file provided");
+                    print_usage();
+                    process::exit(1);
+                }
             }
-            run_file(&args[2]);
         }
         "repl" => {
             run_repl();
         }
+        "-e" | "--eval" => match args.get(2) {
+            Some(source) => run_source(source, &RunOptions::default()),
+            None => {
+                eprintln!("Error: No code provided to {}", args[1]);
+                print_usage();
+                process::exit(1);
+            }
+        },
         "--help" | "-h" => {
             print_usage();
         }
@@ -45,6 +72,17 @@ fn main() {
     }
 }
 
+/// Flags accepted by the `run` command
+#[derive(Default)]
+struct RunOptions {
+    trace: bool,
+    profile: bool,
+    ast: bool,    // print the parsed program instead of running it
+    tokens: bool, // print the lexer's tokens instead of parsing
+    check: bool,  // resolve variables statically and refuse to run on errors
+    optimize: bool, // fold constant expressions before running (or printing the AST)
+}
+
 fn print_usage() {
     println!("Platypus Programming Language v0.1.0");
     println!();
@@ -52,17 +90,28 @@ fn print_usage() {
     println!("    platypus <COMMAND> [OPTIONS]");
     println!();
     println!("COMMANDS:");
-    println!("    run <file>     Compile and execute a Platypus source file");
-    println!("    repl           Start an interactive REPL");
-    println!("    --help, -h     Print this help message");
-    println!("    --version, -v  Print version information");
+    println!("    run <file>         Compile and execute a Platypus source file");
+    println!("    repl               Start an interactive REPL");
+    println!("    -e, --eval <code>  Execute the given source code");
+    println!("    --help, -h         Print this help message");
+    println!("    --version, -v      Print version information");
+    println!();
+    println!("RUN OPTIONS:");
+    println!("    --trace        Log each statement to stderr as it executes");
+    println!("    --profile      Print operation counts to stderr after the program finishes");
+    println!("    --ast          Print the parsed syntax tree instead of running the program");
+    println!("    --tokens       Print the tokens, with their line and column, instead of parsing");
+    println!("    --check        Report undefined variables before running, including in code that never executes");
+    println!("    --optimize     Fold constant expressions such as 2 * 60 before running");
     println!();
     println!("EXAMPLES:");
     println!("    platypus run hello.plat");
+    println!("    platypus run --trace hello.plat");
+    println!("    platypus -e \"print(1 + 2)\"");
     println!("    platypus repl");
 }
 
-fn run_file(filename: &str) {
+fn run_file(filename: &str, options: &RunOptions) {
     let source = match fs::read_to_string(filename) {
         Ok(content) => content,
         Err(err) => {
@@ -71,8 +120,12 @@ fn run_file(filename: &str) {
         }
     };
 
-    if let Err(err) = execute_source(&source) {
-        eprintln!("Error: {}", err);
+    run_source(&source, options);
+}
+
+fn run_source(source: &str, options: &RunOptions) {
+    if let Err(err) = execute_source(source, options) {
+        eprintln!("{}", err);
         process::exit(1);
     }
 }
@@ -86,32 +139,49 @@ fn run_repl() {
     let stdin = io::stdin();
     let mut stdout = io::stdout();
 
+    // Lines of an entry that isn't complete yet, such as a function whose closing
+    // brace hasn't been typed
+    let mut buffer = String::new();
+
     loop {
-        print!(">> ");
+        print!("{}", if buffer.is_empty() { ">> " } else { ".. " });
         stdout.flush().unwrap();
 
         let mut input = String::new();
         match stdin.read_line(&mut input) {
-            Ok(0) => break, // EOF
-            Ok(_) => {
+            Ok(0) if buffer.is_empty() => break, // EOF
+            Ok(read) => {
                 let input = input.trim();
-                if input == "exit" {
-                    break;
+                if buffer.is_empty() {
+                    if input == "exit" {
+                        break;
+                    }
+                    if input.is_empty() {
+                        continue;
+                    }
+                    if input.starts_with(':') {
+                        run_repl_command(&mut interpreter, input);
+                        continue;
+                    }
                 }
-                if input.is_empty() {
+                buffer.push_str(input);
+                buffer.push('\n');
+                // At EOF, run whatever was typed so far rather than waiting for more
+                if read > 0 && is_incomplete(&buffer) {
                     continue;
                 }
+                let entry = std::mem::take(&mut buffer);
 
                 // Try to parse and execute
-                match execute_repl_line(&mut interpreter, input) {
+                match execute_repl_line(&mut interpreter, &entry) {
                     Ok(Some(value)) => {
                         // Only print if it's not null
                         if !matches!(value, runtime::value::Value::Null) {
-                            println!("{}", value);
+                            println!("{}", interpreter.format_value(&value));
                         }
                     }
                     Ok(None) => {}
-                    Err(err) => eprintln!("Error: {}", err),
+                    Err(err) => eprintln!("Error: {}", err.message),
                 }
             }
             Err(err) => {
@@ -124,23 +194,100 @@ fn run_repl() {
     println!("Goodbye!");
 }
 
-fn execute_source(source: &str) -> Result<(), String> {
+/// Colon-prefixed REPL commands, handled instead of being parsed as code
+fn run_repl_command(interpreter: &mut Interpreter, input: &str) {
+    let (command, argument) = input.split_once(' ').map_or((input, ""), |(c, a)| (c, a.trim()));
+    match (command, argument) {
+        (":help", "") => {
+            println!(":help          Show this list of commands");
+            println!(":vars          Show the global variables and their values");
+            println!(":clear         Forget all variables and start a fresh session");
+            println!(":load <file>   Run a file in the current session");
+            println!("exit           Leave the REPL");
+        }
+        (":vars", "") => {
+            for (name, value) in interpreter.global_bindings() {
+                println!("{} = {}", name, value);
+            }
+        }
+        (":clear", "") => *interpreter = Interpreter::new(),
+        (":load", "") => eprintln!("Error: :load expects a file name"),
+        (":load", filename) => match fs::read_to_string(filename) {
+            Ok(source) => {
+                if let Err(err) = execute_repl_line(interpreter, &source) {
+                    eprintln!("Error: {}", err.message);
+                }
+            }
+            Err(err) => eprintln!("Error reading file '{}': {}", filename, err),
+        },
+        _ => eprintln!("Error: Unknown command '{}', type :help for a list", input),
+    }
+}
+
+fn execute_source(source: &str, options: &RunOptions) -> Result<(), RuntimeError> {
     // Lexing
     let mut lexer = Lexer::new(source.to_string());
     let tokens = lexer.tokenize()?;
+    if options.tokens {
+        for token in &tokens {
+            println!("{}:{} {:?}", token.line, token.column, token.token_type);
+        }
+        return Ok(());
+    }
 
     // Parsing
     let mut parser = Parser::new(tokens);
-    let program = parser.parse()?;
+    let mut program = parser.parse()?;
+    if options.optimize {
+        optimize::fold(&mut program);
+    }
+    if options.ast {
+        println!("{:#?}", program);
+        return Ok(());
+    }
 
     // Execution
     let mut interpreter = Interpreter::new();
+    if options.check {
+        if let Err(errors) = interpreter.resolve(&program) {
+            return Err(RuntimeError::from(errors.join("\nError: ")));
+        }
+    }
+    interpreter.set_trace(options.trace);
+    interpreter.set_profile(options.profile);
     interpreter.execute(&program)?;
+    if options.profile {
+        eprintln!("{}", interpreter.profile_report());
+    }
 
     Ok(())
 }
 
-fn execute_repl_line(interpreter: &mut Interpreter, source: &str) -> Result<Option<runtime::value::Value>, String> {
+/// Whether REPL input needs more lines: it has unclosed brackets, or parsing
+/// ran into the end of the input
+fn is_incomplete(source: &str) -> bool {
+    let Ok(tokens) = Lexer::new(source.to_string()).tokenize() else {
+        return false;
+    };
+    let depth: i64 = tokens
+        .iter()
+        .map(|token| match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => 1,
+            TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => -1,
+            _ => 0,
+        })
+        .sum();
+    if depth > 0 {
+        return true;
+    }
+    let eof = tokens.last().map(|token| format!("at line {}, column {}", token.line, token.column));
+    match (Parser::new(tokens).parse(), eof) {
+        (Err(err), Some(eof)) => err.ends_with(&eof),
+        _ => false,
+    }
+}
+
+fn execute_repl_line(interpreter: &mut Interpreter, source: &str) -> Result<Option<runtime::value::Value>, RuntimeError> {
     // Lexing
     let mut lexer = Lexer::new(source.to_string());
     let tokens = lexer.tokenize()?;
@@ -151,8 +298,14 @@ fn execute_repl_line(interpreter: &mut Interpreter, source: &str) -> Result<Opti
 
     // For REPL, if there's a single expression statement, return its value
     if program.statements.len() == 1 {
-        if let parser::ast::Stmt::Expr(expr) = &program.statements[0] {
-            return Ok(Some(interpreter.evaluate_expr(expr)?));
+        if let parser::ast::StmtKind::Expr(_) = &program.statements[0].kind {
+            let value = interpreter.eval(source)?;
+            // Like Python, `_` holds the last result that was shown, so a
+            // null result such as a `print` call leaves it alone
+            if value != runtime::value::Value::Null {
+                interpreter.set_global("_", value.clone());
+            }
+            return Ok(Some(value));
         }
     }
 
@@ -0,0 +1,269 @@
+//! Optional optimizations applied to a parsed program before it runs.
+//!
+//! `fold` replaces operators whose operands are all literals with the literal
+//! they evaluate to, so `2 * 60 * 60` is computed once instead of every time
+//! the expression runs. Only operations that give the same result in strict
+//! and non-strict mode are folded; anything that would fail at runtime, such
+//! as dividing by zero, is left in place so the error still happens there.
+
+use crate::parser::ast::*;
+use std::cmp::Ordering;
+
+/// Fold constant subexpressions throughout `program`
+pub fn fold(program: &mut Program) {
+    for stmt in &mut program.statements {
+        fold_stmt(stmt);
+    }
+}
+
+fn fold_stmt(stmt: &mut Stmt) {
+    match &mut stmt.kind {
+        StmtKind::VarDecl { value, .. } | StmtKind::Let { value, .. } | StmtKind::Destructure { value, .. } => {
+            fold_expr(value)
+        }
+        StmtKind::FuncDecl { params, body, .. } => fold_function(params, body),
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                fold_expr(value);
+            }
+        }
+        StmtKind::Expr(expr) => fold_expr(expr),
+        StmtKind::If { condition, then_branch, else_branch } => {
+            fold_expr(condition);
+            fold_stmt(then_branch);
+            if let Some(else_branch) = else_branch {
+                fold_stmt(else_branch);
+            }
+        }
+        StmtKind::While { condition: expr, body } | StmtKind::Repeat { count: expr, body } => {
+            fold_expr(expr);
+            fold_stmt(body);
+        }
+        StmtKind::For { init, condition, increment, body } => {
+            if let Some(init) = init {
+                fold_stmt(init);
+            }
+            for expr in condition.iter_mut().chain(increment) {
+                fold_expr(expr);
+            }
+            fold_stmt(body);
+        }
+        StmtKind::ForEach { iterable, body, .. } => {
+            fold_expr(iterable);
+            fold_stmt(body);
+        }
+        StmtKind::ClassDecl { methods, properties, .. } => {
+            for property in properties {
+                fold_expr(&mut property.value);
+            }
+            for method in methods {
+                fold_function(&mut method.params, &mut method.body);
+            }
+        }
+        StmtKind::Block(stmts) => {
+            for stmt in stmts {
+                fold_stmt(stmt);
+            }
+        }
+    }
+}
+
+fn fold_function(params: &mut [Param], body: &mut [Stmt]) {
+    for default in params.iter_mut().filter_map(|p| p.default.as_mut()) {
+        fold_expr(default);
+    }
+    for stmt in body {
+        fold_stmt(stmt);
+    }
+}
+
+fn fold_expr(expr: &mut Expr) {
+    match expr {
+        Expr::Literal(_) | Expr::Variable(_) => {}
+        Expr::Template(exprs) | Expr::Array(exprs) => {
+            for expr in exprs {
+                fold_expr(expr);
+            }
+        }
+        Expr::Assign { value, .. } | Expr::TypeCheck { value, .. } | Expr::Spread(value) => fold_expr(value),
+        Expr::Index { object, index } => {
+            fold_expr(object);
+            fold_expr(index);
+        }
+        Expr::IndexAssign { object, index, value, .. } => {
+            fold_expr(object);
+            fold_expr(index);
+            fold_expr(value);
+        }
+        Expr::PropertyAssign { object, value, .. } => {
+            fold_expr(object);
+            fold_expr(value);
+        }
+        Expr::BinaryOp { left, operator, right } => {
+            fold_expr(left);
+            fold_expr(right);
+            if let (Expr::Literal(a), Expr::Literal(b)) = (&**left, &**right) {
+                if let Some(folded) = fold_binary(a, operator, b) {
+                    *expr = Expr::Literal(folded);
+                }
+            }
+        }
+        Expr::UnaryOp { operator, right } => {
+            fold_expr(right);
+            let folded = match (operator, &**right) {
+                (UnaryOp::Negate, Expr::Literal(Literal::Number(n))) => Some(Literal::Number(-n)),
+                (UnaryOp::Not, Expr::Literal(lit)) => Some(Literal::Boolean(!is_truthy(lit))),
+                _ => None,
+            };
+            if let Some(folded) = folded {
+                *expr = Expr::Literal(folded);
+            }
+        }
+        Expr::FunctionCall { args, .. } | Expr::SuperCall { args, .. } => {
+            for arg in args {
+                fold_expr(arg);
+            }
+        }
+        Expr::New { args, fields, .. } => {
+            for arg in args {
+                fold_expr(arg);
+            }
+            for (_, value) in fields.iter_mut().flatten() {
+                fold_expr(value);
+            }
+        }
+        Expr::Lambda { body, .. } => fold_expr(body),
+        Expr::Function { params, body } => fold_function(params, body),
+        Expr::Match { expr, cases } => {
+            fold_expr(expr);
+            for case in cases {
+                if let Some(guard) = &mut case.guard {
+                    fold_expr(guard);
+                }
+                fold_expr(&mut case.body);
+            }
+        }
+        Expr::Map(entries) => {
+            for (_, value) in entries {
+                fold_expr(value);
+            }
+        }
+        Expr::MethodCall { object, args, .. } => {
+            fold_expr(object);
+            for arg in args {
+                fold_expr(arg);
+            }
+        }
+        Expr::PropertyAccess { object, .. } => fold_expr(object),
+    }
+}
+
+/// The literal `left operator right` evaluates to, or None when it should be
+/// left for the interpreter
+fn fold_binary(left: &Literal, operator: &BinaryOp, right: &Literal) -> Option<Literal> {
+    use Literal::{Boolean, Number, String};
+    let ordering = match (left, right) {
+        (Number(a), Number(b)) => a.partial_cmp(b),
+        (String(a), String(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+    Some(match (left, operator, right) {
+        (Number(a), BinaryOp::Add, Number(b)) => Number(a + b),
+        (Number(a), BinaryOp::Subtract, Number(b)) => Number(a - b),
+        (Number(a), BinaryOp::Multiply, Number(b)) => Number(a * b),
+        (Number(a), BinaryOp::Divide, Number(b)) if *b != 0.0 => Number(a / b),
+        (Number(a), BinaryOp::Power, Number(b)) => Number(a.powf(*b)),
+        (String(a), BinaryOp::Add, String(b)) => String(format!("{}{}", a, b)),
+        (_, BinaryOp::Less, _) => Boolean(ordering? == Ordering::Less),
+        (_, BinaryOp::LessEqual, _) => Boolean(ordering? != Ordering::Greater),
+        (_, BinaryOp::Greater, _) => Boolean(ordering? == Ordering::Greater),
+        (_, BinaryOp::GreaterEqual, _) => Boolean(ordering? != Ordering::Less),
+        (_, BinaryOp::Equal, _) => Boolean(left == right),
+        (_, BinaryOp::NotEqual, _) => Boolean(left != right),
+        (_, BinaryOp::And, _) => Boolean(is_truthy(left) && is_truthy(right)),
+        (_, BinaryOp::Or, _) => Boolean(is_truthy(left) || is_truthy(right)),
+        (Literal::Null, BinaryOp::Coalesce, _) => right.clone(),
+        (_, BinaryOp::Coalesce, _) => left.clone(),
+        _ => return None,
+    })
+}
+
+/// `Value::is_truthy` for a literal
+fn is_truthy(literal: &Literal) -> bool {
+    match literal {
+        Literal::Null => false,
+        Literal::Boolean(b) => *b,
+        Literal::Number(n) => *n != 0.0,
+        Literal::String(s) => !s.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::value::Value;
+    use crate::{Interpreter, Lexer, Parser};
+
+    fn parse(source: &str) -> Program {
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    fn folded_value(source: &str) -> Expr {
+        let mut program = parse(source);
+        fold(&mut program);
+        match program.statements.remove(0).kind {
+            StmtKind::VarDecl { value, .. } => value,
+            other => panic!("Expected assignment, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_folds_constant_subtrees() {
+        assert_eq!(folded_value("x = 2 * 60 * 60"), Expr::Literal(Literal::Number(7200.0)));
+        assert_eq!(folded_value("x = -(2 ** 3) + 1"), Expr::Literal(Literal::Number(-7.0)));
+        assert_eq!(folded_value("x = \"a\" + \"b\" == \"ab\""), Expr::Literal(Literal::Boolean(true)));
+        assert_eq!(folded_value("x = !(1 < 2) || null ?? 5"), Expr::Literal(Literal::Boolean(false)));
+        assert_eq!(
+            folded_value("x = n * (24 * 60)"),
+            Expr::BinaryOp {
+                left: Box::new(Expr::Variable("n".to_string())),
+                operator: BinaryOp::Multiply,
+                right: Box::new(Expr::Literal(Literal::Number(1440.0))),
+            }
+        );
+    }
+
+    #[test]
+    fn test_leaves_failing_operations_unfolded() {
+        let unfolded = |source: &str| {
+            let expr = folded_value(source);
+            assert!(matches!(expr, Expr::BinaryOp { .. }), "{} folded to {:?}", source, expr);
+        };
+        unfolded("x = 1 / (2 - 2)");
+        unfolded("x = \"5\" - 3"); // an error in strict mode
+        unfolded("x = 1 + \"a\"");
+        unfolded("x = 1.5 & 1");
+    }
+
+    #[test]
+    fn test_folding_preserves_results() {
+        let source = "func area(r = 2 * 3) { return 3 * r * r }
+                      total = 0
+                      for (i = 0; i < 2 + 1; i += 1) { total += 10 / 4 }
+                      kind = match (4) { case n if n > 2 * 1 => \"big\" case _ => \"small\" }
+                      text = \"minutes: ${60 * 24}\"
+                      checks = [area(), 1 == 1.0, \"b\" > \"a\", !0, 0 ?? 1, 2 ** -1]";
+        let run = |optimize: bool| {
+            let mut program = parse(source);
+            if optimize {
+                fold(&mut program);
+            }
+            let mut interpreter = Interpreter::new();
+            interpreter.execute(&program).unwrap();
+            ["total", "kind", "text", "checks"].map(|name| interpreter.eval(name).unwrap())
+        };
+        assert_eq!(run(true), run(false));
+        assert_eq!(run(true)[0], Value::Number(7.5));
+    }
+}
@@ -4,14 +4,36 @@ pub struct Program {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Stmt {
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub line: usize, // source line the statement starts on
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, line: usize) -> Self {
+        Stmt { kind, line }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
     VarDecl {
         name: String,
         value: Expr,
     },
+    Let {
+        name: String,
+        value: Expr,
+    },
+    /// `[a, b, ...rest] = value`: binds each name to the matching array element
+    Destructure {
+        names: Vec<String>,
+        rest: Option<String>,
+        value: Expr,
+    },
     FuncDecl {
         name: String,
-        params: Vec<String>,
+        params: Vec<Param>,
         return_type: Option<String>,
         body: Vec<Stmt>,
     },
@@ -26,6 +48,10 @@ pub enum Stmt {
         condition: Expr,
         body: Box<Stmt>,
     },
+    Repeat {
+        count: Expr,
+        body: Box<Stmt>,
+    },
     For {
         init: Option<Box<Stmt>>,
         condition: Option<Expr>,
@@ -33,6 +59,7 @@ pub enum Stmt {
         body: Box<Stmt>,
     },
     ForEach {
+        index: Option<String>, // `for (i, item in items)` also binds the position
         variable: String,
         iterable: Expr,
         body: Box<Stmt>,
@@ -40,20 +67,79 @@ pub enum Stmt {
     ClassDecl {
         name: String,
         extends: Option<String>,
-        methods: Vec<(String, Vec<String>, Option<String>, Vec<Stmt>)>, // name, params, return_type, body
-        properties: Vec<(String, Expr)>, // name, default_value
+        methods: Vec<MethodDecl>,
+        properties: Vec<PropertyDecl>,
     },
     Block(Vec<Stmt>),
 }
 
+impl StmtKind {
+    /// Short name of the statement kind, used by tracing
+    pub fn name(&self) -> &'static str {
+        match self {
+            StmtKind::VarDecl { .. } => "VarDecl",
+            StmtKind::Let { .. } => "Let",
+            StmtKind::Destructure { .. } => "Destructure",
+            StmtKind::FuncDecl { .. } => "FuncDecl",
+            StmtKind::Return(_) => "Return",
+            StmtKind::Expr(_) => "Expr",
+            StmtKind::If { .. } => "If",
+            StmtKind::While { .. } => "While",
+            StmtKind::Repeat { .. } => "Repeat",
+            StmtKind::For { .. } => "For",
+            StmtKind::ForEach { .. } => "ForEach",
+            StmtKind::ClassDecl { .. } => "ClassDecl",
+            StmtKind::Block(_) => "Block",
+        }
+    }
+}
+
+/// A function or method parameter, optionally with a default value used when
+/// the caller leaves it out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub default: Option<Expr>,
+    pub rest: bool, // `...name` collects the remaining arguments into an array
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodDecl {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub return_type: Option<String>,
+    pub body: Vec<Stmt>,
+    pub is_override: bool, // declared with `override`; must replace a parent method
+    pub is_private: bool,  // declared with `private`; only callable from the class's own methods
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyDecl {
+    pub name: String,
+    pub value: Expr, // default value
+    pub is_const: bool,   // can only be assigned while the object is being constructed
+    pub is_private: bool, // only accessible from the class's own methods
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Literal(Literal),
+    Template(Vec<Expr>), // interpolated string: parts are displayed and concatenated
     Variable(String),
     Assign {
         name: String,
         value: Box<Expr>,
     },
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+    },
+    IndexAssign {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        operator: Option<BinaryOp>, // set for compound assignment such as `arr[i] += 1`
+        value: Box<Expr>,
+    },
     PropertyAssign {
         object: Box<Expr>,
         property: String,
@@ -76,23 +162,40 @@ pub enum Expr {
         params: Vec<String>,
         body: Box<Expr>,
     },
+    Function {
+        params: Vec<Param>,
+        body: Vec<Stmt>, // anonymous `func(params) { ... }`
+    },
     Match {
         expr: Box<Expr>,
         cases: Vec<MatchCase>,
     },
     Array(Vec<Expr>),
+    Spread(Box<Expr>), // `...items` inside an array literal
+    Map(Vec<(String, Expr)>), // key, value
     New {
         class_name: String,
         args: Vec<Expr>,
+        fields: Option<Vec<(String, Expr)>>, // `new Point { x: 1 }`: set these instead of calling init
     },
     MethodCall {
         object: Box<Expr>,
         method: String,
         args: Vec<Expr>,
+        optional: bool, // `obj?.method()`: null when obj is null
+    },
+    TypeCheck {
+        value: Box<Expr>,
+        type_name: String, // a type name such as Number, or a class name
+    },
+    SuperCall {
+        method: Option<String>, // None for `super(args)`, which calls the parent's init
+        args: Vec<Expr>,
     },
     PropertyAccess {
         object: Box<Expr>,
         property: String,
+        optional: bool, // `obj?.property`: null when obj is null
     },
 }
 
@@ -110,6 +213,7 @@ pub enum BinaryOp {
     Subtract,
     Multiply,
     Divide,
+    Power, // `**`, right-associative and tighter than unary minus on its left
     Equal,
     NotEqual,
     Less,
@@ -118,17 +222,26 @@ pub enum BinaryOp {
     GreaterEqual,
     And,
     Or,
+    Coalesce, // `??`: the left value unless it is null
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    In, // `x in collection`: array element, substring, or map key / object property
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOp {
     Not,
     Negate,
+    BitNot,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MatchCase {
     pub pattern: Pattern,
+    pub guard: Option<Expr>,
     pub body: Expr,
 }
 
@@ -137,4 +250,219 @@ pub enum Pattern {
     Literal(Literal),
     Identifier(String),
     Wildcard,
+    Or(Vec<Pattern>), // `case 1, 2, 3 =>`; never binds a variable
+}
+
+/// Traverses the AST for tools such as linters and analyzers. The default
+/// methods visit every child node; override the ones a tool cares about and
+/// call the matching `walk_*` function to keep descending
+pub trait Visitor {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for stmt in &program.statements {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+/// Visit the statements and expressions directly inside a statement
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::VarDecl { value, .. } | StmtKind::Let { value, .. } | StmtKind::Destructure { value, .. } => {
+            visitor.visit_expr(value)
+        }
+        StmtKind::FuncDecl { params, body, .. } => {
+            walk_params(visitor, params);
+            for stmt in body {
+                visitor.visit_stmt(stmt);
+            }
+        }
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        StmtKind::Expr(expr) => visitor.visit_expr(expr),
+        StmtKind::If { condition, then_branch, else_branch } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_stmt(else_branch);
+            }
+        }
+        StmtKind::While { condition: expr, body } | StmtKind::Repeat { count: expr, body } => {
+            visitor.visit_expr(expr);
+            visitor.visit_stmt(body);
+        }
+        StmtKind::For { init, condition, increment, body } => {
+            if let Some(init) = init {
+                visitor.visit_stmt(init);
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expr(condition);
+            }
+            if let Some(increment) = increment {
+                visitor.visit_expr(increment);
+            }
+            visitor.visit_stmt(body);
+        }
+        StmtKind::ForEach { iterable, body, .. } => {
+            visitor.visit_expr(iterable);
+            visitor.visit_stmt(body);
+        }
+        StmtKind::ClassDecl { methods, properties, .. } => {
+            for property in properties {
+                visitor.visit_expr(&property.value);
+            }
+            for method in methods {
+                walk_params(visitor, &method.params);
+                for stmt in &method.body {
+                    visitor.visit_stmt(stmt);
+                }
+            }
+        }
+        StmtKind::Block(stmts) => {
+            for stmt in stmts {
+                visitor.visit_stmt(stmt);
+            }
+        }
+    }
+}
+
+fn walk_params<V: Visitor + ?Sized>(visitor: &mut V, params: &[Param]) {
+    for default in params.iter().filter_map(|p| p.default.as_ref()) {
+        visitor.visit_expr(default);
+    }
+}
+
+/// Visit the expressions directly inside an expression
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Literal(_) | Expr::Variable(_) => {}
+        Expr::Template(exprs) | Expr::Array(exprs) => {
+            for expr in exprs {
+                visitor.visit_expr(expr);
+            }
+        }
+        Expr::Assign { value, .. } => visitor.visit_expr(value),
+        Expr::Index { object, index } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+        }
+        Expr::IndexAssign { object, index, value, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+            visitor.visit_expr(value);
+        }
+        Expr::PropertyAssign { object, value, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(value);
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::UnaryOp { right, .. } => visitor.visit_expr(right),
+        Expr::FunctionCall { args, .. } | Expr::SuperCall { args, .. } => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::New { args, fields, .. } => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+            for (_, value) in fields.iter().flatten() {
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::Lambda { body, .. } => visitor.visit_expr(body),
+        Expr::Function { params, body } => {
+            walk_params(visitor, params);
+            for stmt in body {
+                visitor.visit_stmt(stmt);
+            }
+        }
+        Expr::Match { expr, cases } => {
+            visitor.visit_expr(expr);
+            for case in cases {
+                if let Some(guard) = &case.guard {
+                    visitor.visit_expr(guard);
+                }
+                visitor.visit_expr(&case.body);
+            }
+        }
+        Expr::Map(entries) => {
+            for (_, value) in entries {
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::MethodCall { object, args, .. } => {
+            visitor.visit_expr(object);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::TypeCheck { value, .. } | Expr::Spread(value) => visitor.visit_expr(value),
+        Expr::PropertyAccess { object, .. } => visitor.visit_expr(object),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[derive(Default)]
+    struct Counter {
+        functions: usize,
+        calls: Vec<String>,
+    }
+
+    impl Visitor for Counter {
+        fn visit_stmt(&mut self, stmt: &Stmt) {
+            if let StmtKind::FuncDecl { .. } = stmt.kind {
+                self.functions += 1;
+            }
+            walk_stmt(self, stmt);
+        }
+
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let Expr::FunctionCall { name, .. } = expr {
+                self.calls.push(name.clone());
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    #[test]
+    fn test_visitor_counts_functions() {
+        let source = r#"
+            func outer(x = inner_default()) {
+                func inner() {
+                    return [helper(x)]
+                }
+                return inner()
+            }
+            if (true) {
+                func nested() { }
+            }
+            print(match (1) { case _ => outer() })
+        "#;
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let mut counter = Counter::default();
+        walk_program(&mut counter, &program);
+        assert_eq!(counter.functions, 3);
+        assert_eq!(counter.calls, vec!["inner_default", "helper", "inner", "print", "outer"]);
+    }
 }
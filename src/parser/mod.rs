@@ -1,16 +1,18 @@
 pub mod ast;
 
-use crate::lexer::token::{Token, TokenType};
+use crate::lexer::token::{TemplatePart, Token, TokenType};
+use crate::lexer::Lexer;
 use ast::*;
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    in_guard: bool, // parsing a match guard, whose `=>` starts the case body rather than a lambda
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser { tokens, current: 0, in_guard: false }
     }
 
     fn peek(&self) -> &Token {
@@ -67,16 +69,19 @@ impl Parser {
     }
 
     fn declaration(&mut self) -> Result<Stmt, String> {
-        if self.match_token(&[TokenType::Func]) {
-            self.function_declaration()
+        let line = self.peek().line;
+        // `func(` starts an anonymous function expression rather than a declaration
+        let anonymous = self.tokens.get(self.current + 1).is_some_and(|t| t.token_type == TokenType::LeftParen);
+        if !anonymous && self.match_token(&[TokenType::Func]) {
+            Ok(Stmt::new(self.function_declaration()?, line))
         } else if self.match_token(&[TokenType::Class]) {
-            self.class_declaration()
+            Ok(Stmt::new(self.class_declaration()?, line))
         } else {
             self.statement()
         }
     }
 
-    fn function_declaration(&mut self) -> Result<Stmt, String> {
+    fn function_declaration(&mut self) -> Result<StmtKind, String> {
         let name = if let TokenType::Identifier(id) = &self.peek().token_type {
             let n = id.clone();
             self.advance();
@@ -86,23 +91,7 @@ impl Parser {
         };
 
         self.consume(TokenType::LeftParen, "Expected '(' after function name")?;
-        
-        let mut params = Vec::new();
-        if !self.check(&TokenType::RightParen) {
-            loop {
-                if let TokenType::Identifier(id) = &self.peek().token_type {
-                    params.push(id.clone());
-                    self.advance();
-                } else {
-                    return Err(format!("Expected parameter name at line {}", self.peek().line));
-                }
-
-                if !self.match_token(&[TokenType::Comma]) {
-                    break;
-                }
-            }
-        }
-
+        let params = self.parameters()?;
         self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
 
         // Optional return type annotation
@@ -127,7 +116,7 @@ impl Parser {
 
         self.consume(TokenType::RightBrace, "Expected '}' after function body")?;
 
-        Ok(Stmt::FuncDecl {
+        Ok(StmtKind::FuncDecl {
             name,
             params,
             return_type,
@@ -135,7 +124,51 @@ impl Parser {
         })
     }
 
-    fn class_declaration(&mut self) -> Result<Stmt, String> {
+    /// Parse a parameter list up to (not including) the closing paren. Parameters
+    /// may have defaults (`greeting = "Hi"`), but only after the required ones,
+    /// and the last one may be a rest parameter (`...values`)
+    fn parameters(&mut self) -> Result<Vec<Param>, String> {
+        let mut params: Vec<Param> = Vec::new();
+        if self.check(&TokenType::RightParen) {
+            return Ok(params);
+        }
+
+        loop {
+            let line = self.peek().line;
+            let rest = self.match_token(&[TokenType::Ellipsis]);
+            let name = if let TokenType::Identifier(id) = &self.peek().token_type {
+                let n = id.clone();
+                self.advance();
+                n
+            } else {
+                return Err(format!("Expected parameter name at line {}", line));
+            };
+
+            let default = if rest {
+                if self.check(&TokenType::Assign) {
+                    return Err(format!("Rest parameter '{}' can't have a default value at line {}", name, line));
+                }
+                None
+            } else if self.match_token(&[TokenType::Assign]) {
+                Some(self.expression()?)
+            } else if params.iter().any(|p| p.default.is_some()) {
+                return Err(format!("Parameter '{}' needs a default value because an earlier parameter has one at line {}", name, line));
+            } else {
+                None
+            };
+            params.push(Param { name, default, rest });
+
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
+            }
+            if rest {
+                return Err(format!("Rest parameter must be the last parameter at line {}", line));
+            }
+        }
+        Ok(params)
+    }
+
+    fn class_declaration(&mut self) -> Result<StmtKind, String> {
         let name = if let TokenType::Identifier(id) = &self.peek().token_type {
             let n = id.clone();
             self.advance();
@@ -163,6 +196,32 @@ impl Parser {
         let mut properties = Vec::new();
 
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            let mut is_override = false;
+            let mut is_const = false;
+            let mut visibility = None;
+            loop {
+                let line = self.peek().line;
+                if self.match_token(&[TokenType::Override]) {
+                    is_override = true;
+                } else if self.match_token(&[TokenType::Const]) {
+                    is_const = true;
+                } else if self.match_token(&[TokenType::Public, TokenType::Private]) {
+                    if visibility.is_some() {
+                        return Err(format!("Class member has more than one visibility modifier at line {}", line));
+                    }
+                    visibility = Some(self.previous().token_type == TokenType::Private);
+                } else {
+                    break;
+                }
+            }
+            let is_private = visibility.unwrap_or(false);
+            if is_override && !self.check(&TokenType::Func) {
+                return Err(format!("Expected 'func' after 'override' at line {}", self.peek().line));
+            }
+            if is_const && self.check(&TokenType::Func) {
+                return Err(format!("Methods can't be 'const' at line {}", self.peek().line));
+            }
+
             if self.match_token(&[TokenType::Func]) {
                 // Parse method
                 let method_name = if let TokenType::Identifier(id) = &self.peek().token_type {
@@ -174,22 +233,7 @@ impl Parser {
                 };
 
                 self.consume(TokenType::LeftParen, "Expected '(' after method name")?;
-                
-                let mut params = Vec::new();
-                if !self.check(&TokenType::RightParen) {
-                    loop {
-                        if let TokenType::Identifier(id) = &self.peek().token_type {
-                            params.push(id.clone());
-                            self.advance();
-                        } else {
-                            return Err(format!("Expected parameter name at line {}", self.peek().line));
-                        }
-
-                        if !self.match_token(&[TokenType::Comma]) {
-                            break;
-                        }
-                    }
-                }
+                let params = self.parameters()?;
 
                 self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
 
@@ -215,19 +259,31 @@ impl Parser {
 
                 self.consume(TokenType::RightBrace, "Expected '}' after method body")?;
 
-                methods.push((method_name, params, return_type, body));
+                methods.push(MethodDecl {
+                    name: method_name,
+                    params,
+                    return_type,
+                    body,
+                    is_override,
+                    is_private,
+                });
             } else {
                 // Parse property
                 if let TokenType::Identifier(prop_name) = &self.peek().token_type {
                     let p = prop_name.clone();
                     self.advance();
                     
-                    if self.match_token(&[TokenType::Assign]) {
-                        let expr = self.expression()?;
-                        properties.push((p, expr));
+                    let value = if self.match_token(&[TokenType::Assign]) {
+                        self.expression()?
                     } else {
-                        properties.push((p, Expr::Literal(Literal::Null)));
-                    }
+                        Expr::Literal(Literal::Null)
+                    };
+                    properties.push(PropertyDecl {
+                        name: p,
+                        value,
+                        is_const,
+                        is_private,
+                    });
 
                     if self.match_token(&[TokenType::Semicolon]) {
                         // Optional semicolon
@@ -240,7 +296,7 @@ impl Parser {
 
         self.consume(TokenType::RightBrace, "Expected '}' after class body")?;
 
-        Ok(Stmt::ClassDecl {
+        Ok(StmtKind::ClassDecl {
             name,
             extends,
             methods,
@@ -249,31 +305,55 @@ impl Parser {
     }
 
     fn statement(&mut self) -> Result<Stmt, String> {
-        if self.match_token(&[TokenType::Return]) {
-            self.return_statement()
+        let line = self.peek().line;
+        let kind = if self.match_token(&[TokenType::Return]) {
+            self.return_statement()?
+        } else if self.match_token(&[TokenType::Let]) {
+            self.let_statement()?
         } else if self.match_token(&[TokenType::If]) {
-            self.if_statement()
+            self.if_statement()?
         } else if self.match_token(&[TokenType::While]) {
-            self.while_statement()
+            self.while_statement()?
+        } else if self.match_token(&[TokenType::Repeat]) {
+            self.repeat_statement()?
         } else if self.match_token(&[TokenType::For]) {
-            self.for_statement()
+            self.for_statement()?
         } else if self.match_token(&[TokenType::LeftBrace]) {
-            Ok(Stmt::Block(self.block_statement()?))
+            StmtKind::Block(self.block_statement()?)
         } else {
-            self.expression_statement()
-        }
+            self.expression_statement()?
+        };
+        Ok(Stmt::new(kind, line))
     }
 
-    fn return_statement(&mut self) -> Result<Stmt, String> {
-        let value = if !self.check(&TokenType::RightBrace) {
+    fn let_statement(&mut self) -> Result<StmtKind, String> {
+        let name = if let TokenType::Identifier(id) = &self.peek().token_type {
+            let n = id.clone();
+            self.advance();
+            n
+        } else {
+            return Err(format!("Expected variable name after 'let' at line {}", self.peek().line));
+        };
+
+        self.consume(TokenType::Assign, "Expected '=' after variable name")?;
+        let value = self.expression()?;
+
+        Ok(StmtKind::Let { name, value })
+    }
+
+    fn return_statement(&mut self) -> Result<StmtKind, String> {
+        // A value has to start on the same line as `return`; a bare `return`
+        // returns null instead of swallowing the next statement
+        let same_line = self.peek().line == self.previous().line;
+        let value = if same_line && !self.is_at_end() && !self.check(&TokenType::RightBrace) {
             Some(self.expression()?)
         } else {
             None
         };
-        Ok(Stmt::Return(value))
+        Ok(StmtKind::Return(value))
     }
 
-    fn if_statement(&mut self) -> Result<Stmt, String> {
+    fn if_statement(&mut self) -> Result<StmtKind, String> {
         self.consume(TokenType::LeftParen, "Expected '(' after 'if'")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expected ')' after condition")?;
@@ -285,38 +365,60 @@ impl Parser {
             None
         };
 
-        Ok(Stmt::If {
+        Ok(StmtKind::If {
             condition,
             then_branch,
             else_branch,
         })
     }
 
-    fn while_statement(&mut self) -> Result<Stmt, String> {
+    fn while_statement(&mut self) -> Result<StmtKind, String> {
         self.consume(TokenType::LeftParen, "Expected '(' after 'while'")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expected ')' after condition")?;
 
         let body = Box::new(self.statement()?);
 
-        Ok(Stmt::While { condition, body })
+        Ok(StmtKind::While { condition, body })
     }
 
-    fn for_statement(&mut self) -> Result<Stmt, String> {
+    fn repeat_statement(&mut self) -> Result<StmtKind, String> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'repeat'")?;
+        let count = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after repeat count")?;
+
+        let line = self.peek().line;
+        self.consume(TokenType::LeftBrace, "Expected '{' after repeat count")?;
+        let body = Box::new(Stmt::new(StmtKind::Block(self.block_statement()?), line));
+
+        Ok(StmtKind::Repeat { count, body })
+    }
+
+    fn for_statement(&mut self) -> Result<StmtKind, String> {
         self.consume(TokenType::LeftParen, "Expected '(' after 'for'")?;
 
-        // Check if this is a foreach loop (for variable in iterable)
+        // Check if this is a foreach loop (for variable in iterable), optionally
+        // with an index variable (for index, variable in iterable)
         if let TokenType::Identifier(var_name) = &self.peek().token_type {
             let temp_pos = self.current;
-            let var_name = var_name.clone();
+            let mut var_name = var_name.clone();
             self.advance();
-            
+
+            let mut index = None;
+            if self.check(&TokenType::Comma) {
+                if let Some(TokenType::Identifier(item_name)) = self.tokens.get(self.current + 1).map(|t| &t.token_type) {
+                    index = Some(std::mem::replace(&mut var_name, item_name.clone()));
+                    self.current += 2;
+                }
+            }
+
             if self.match_token(&[TokenType::In]) {
                 // This is a foreach loop
                 let iterable = self.expression()?;
                 self.consume(TokenType::RightParen, "Expected ')' after foreach")?;
                 let body = Box::new(self.statement()?);
-                return Ok(Stmt::ForEach {
+                return Ok(StmtKind::ForEach {
+                    index,
                     variable: var_name,
                     iterable,
                     body,
@@ -351,7 +453,7 @@ impl Parser {
 
         let body = Box::new(self.statement()?);
 
-        Ok(Stmt::For {
+        Ok(StmtKind::For {
             init,
             condition,
             increment,
@@ -368,18 +470,53 @@ impl Parser {
         Ok(statements)
     }
 
-    fn expression_statement(&mut self) -> Result<Stmt, String> {
+    fn expression_statement(&mut self) -> Result<StmtKind, String> {
+        if self.check(&TokenType::LeftBracket) {
+            let start_pos = self.current;
+            if let Expr::Array(elements) = self.coalesce()? {
+                if self.match_token(&[TokenType::Assign]) {
+                    return self.destructure(elements);
+                }
+            }
+            self.current = start_pos;
+        }
+
         let expr = self.expression()?;
         
         // Check if this is a variable declaration (assignment)
         if let Expr::Assign { name, value } = expr {
-            Ok(Stmt::VarDecl {
+            Ok(StmtKind::VarDecl {
                 name,
                 value: *value,
             })
         } else {
-            Ok(Stmt::Expr(expr))
+            Ok(StmtKind::Expr(expr))
+        }
+    }
+
+    /// The rest of `[a, b, ...rest] = value`, once the target array literal and
+    /// the `=` have been parsed
+    fn destructure(&mut self, elements: Vec<Expr>) -> Result<StmtKind, String> {
+        let line = self.previous().line;
+        let mut names = Vec::new();
+        let mut rest = None;
+        let count = elements.len();
+        for (i, element) in elements.into_iter().enumerate() {
+            match element {
+                Expr::Variable(name) if rest.is_none() => names.push(name),
+                Expr::Spread(inner) if i == count - 1 => match *inner {
+                    Expr::Variable(name) => rest = Some(name),
+                    _ => return Err(format!("Invalid destructuring target at line {}", line)),
+                },
+                Expr::Spread(_) => {
+                    return Err(format!("Only the last destructuring target can use '...' at line {}", line))
+                }
+                _ => return Err(format!("Invalid destructuring target at line {}", line)),
+            }
         }
+
+        let value = self.expression()?;
+        Ok(StmtKind::Destructure { names, rest, value })
     }
 
     fn expression(&mut self) -> Result<Expr, String> {
@@ -387,7 +524,54 @@ impl Parser {
     }
 
     fn assignment(&mut self) -> Result<Expr, String> {
-        let expr = self.or()?;
+        let expr = self.coalesce()?;
+
+        if self.match_token(&[
+            TokenType::PlusAssign,
+            TokenType::MinusAssign,
+            TokenType::StarAssign,
+            TokenType::SlashAssign,
+        ]) {
+            let operator = match &self.previous().token_type {
+                TokenType::PlusAssign => BinaryOp::Add,
+                TokenType::MinusAssign => BinaryOp::Subtract,
+                TokenType::StarAssign => BinaryOp::Multiply,
+                TokenType::SlashAssign => BinaryOp::Divide,
+                _ => unreachable!(),
+            };
+            let value = Box::new(self.assignment()?);
+            // `target op= value` is `target = target op value`, except that an index
+            // expression must only be evaluated once
+            let combined = |target: Expr| {
+                Box::new(Expr::BinaryOp {
+                    left: Box::new(target),
+                    operator: operator.clone(),
+                    right: value.clone(),
+                })
+            };
+            return match expr {
+                Expr::Variable(name) => Ok(Expr::Assign {
+                    value: combined(Expr::Variable(name.clone())),
+                    name,
+                }),
+                Expr::PropertyAccess { object, property, optional: false } => Ok(Expr::PropertyAssign {
+                    value: combined(Expr::PropertyAccess {
+                        object: object.clone(),
+                        property: property.clone(),
+                        optional: false,
+                    }),
+                    object,
+                    property,
+                }),
+                Expr::Index { object, index } => Ok(Expr::IndexAssign {
+                    object,
+                    index,
+                    operator: Some(operator),
+                    value,
+                }),
+                _ => Err("Invalid assignment target".to_string()),
+            };
+        }
 
         if self.match_token(&[TokenType::Assign]) {
             let value = Box::new(self.assignment()?);
@@ -395,7 +579,15 @@ impl Parser {
                 Expr::Variable(name) => {
                     return Ok(Expr::Assign { name, value });
                 }
-                Expr::PropertyAccess { object, property } => {
+                Expr::Index { object, index } => {
+                    return Ok(Expr::IndexAssign {
+                        object,
+                        index,
+                        operator: None,
+                        value,
+                    });
+                }
+                Expr::PropertyAccess { object, property, optional: false } => {
                     // Property assignment: obj.prop = value
                     return Ok(Expr::PropertyAssign {
                         object,
@@ -412,6 +604,22 @@ impl Parser {
         Ok(expr)
     }
 
+    /// `a ?? b`, binding looser than `||` so that `a || b ?? c` is `(a || b) ?? c`
+    fn coalesce(&mut self) -> Result<Expr, String> {
+        let mut expr = self.or()?;
+
+        while self.match_token(&[TokenType::QuestionQuestion]) {
+            let right = Box::new(self.or()?);
+            expr = Expr::BinaryOp {
+                left: Box::new(expr),
+                operator: BinaryOp::Coalesce,
+                right,
+            };
+        }
+
+        Ok(expr)
+    }
+
     fn or(&mut self) -> Result<Expr, String> {
         let mut expr = self.and()?;
 
@@ -429,11 +637,11 @@ impl Parser {
     }
 
     fn and(&mut self) -> Result<Expr, String> {
-        let mut expr = self.equality()?;
+        let mut expr = self.bit_or()?;
 
         while self.match_token(&[TokenType::And]) {
             let operator = BinaryOp::And;
-            let right = Box::new(self.equality()?);
+            let right = Box::new(self.bit_or()?);
             expr = Expr::BinaryOp {
                 left: Box::new(expr),
                 operator,
@@ -444,6 +652,53 @@ impl Parser {
         Ok(expr)
     }
 
+    // Bitwise operators bind like C: `|` looser than `^`, looser than `&`, and all
+    // three looser than equality, so `flags & MASK == 0` is `flags & (MASK == 0)`
+    fn bit_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.bit_xor()?;
+
+        while self.match_token(&[TokenType::Pipe]) {
+            let right = Box::new(self.bit_xor()?);
+            expr = Expr::BinaryOp {
+                left: Box::new(expr),
+                operator: BinaryOp::BitOr,
+                right,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, String> {
+        let mut expr = self.bit_and()?;
+
+        while self.match_token(&[TokenType::Caret]) {
+            let right = Box::new(self.bit_and()?);
+            expr = Expr::BinaryOp {
+                left: Box::new(expr),
+                operator: BinaryOp::BitXor,
+                right,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.equality()?;
+
+        while self.match_token(&[TokenType::Ampersand]) {
+            let right = Box::new(self.equality()?);
+            expr = Expr::BinaryOp {
+                left: Box::new(expr),
+                operator: BinaryOp::BitAnd,
+                right,
+            };
+        }
+
+        Ok(expr)
+    }
+
     fn equality(&mut self) -> Result<Expr, String> {
         let mut expr = self.comparison()?;
 
@@ -465,19 +720,57 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> Result<Expr, String> {
-        let mut expr = self.term()?;
+        let mut expr = self.shift()?;
 
-        while self.match_token(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
+        loop {
+            if self.match_token(&[TokenType::Is]) {
+                let type_name = match &self.peek().token_type {
+                    TokenType::Identifier(name) => name.clone(),
+                    _ => return Err(format!("Expected type or class name after 'is' at line {}", self.peek().line)),
+                };
+                self.advance();
+                expr = Expr::TypeCheck {
+                    value: Box::new(expr),
+                    type_name,
+                };
+                continue;
+            }
+            if !self.match_token(&[
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+                TokenType::In,
+            ]) {
+                break;
+            }
             let operator = match &self.previous().token_type {
                 TokenType::Greater => BinaryOp::Greater,
                 TokenType::GreaterEqual => BinaryOp::GreaterEqual,
                 TokenType::Less => BinaryOp::Less,
                 TokenType::LessEqual => BinaryOp::LessEqual,
+                TokenType::In => BinaryOp::In,
+                _ => unreachable!(),
+            };
+            let right = Box::new(self.shift()?);
+            expr = Expr::BinaryOp {
+                left: Box::new(expr),
+                operator,
+                right,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// `<<` and `>>`, binding tighter than comparisons but looser than `+` and `-`
+    fn shift(&mut self) -> Result<Expr, String> {
+        let mut expr = self.term()?;
+
+        while self.match_token(&[TokenType::ShiftLeft, TokenType::ShiftRight]) {
+            let operator = match &self.previous().token_type {
+                TokenType::ShiftLeft => BinaryOp::ShiftLeft,
+                TokenType::ShiftRight => BinaryOp::ShiftRight,
                 _ => unreachable!(),
             };
             let right = Box::new(self.term()?);
@@ -532,26 +825,68 @@ impl Parser {
     }
 
     fn unary(&mut self) -> Result<Expr, String> {
-        if self.match_token(&[TokenType::Bang, TokenType::Minus]) {
+        if self.match_token(&[TokenType::Bang, TokenType::Minus, TokenType::Tilde]) {
             let operator = match &self.previous().token_type {
                 TokenType::Bang => UnaryOp::Not,
                 TokenType::Minus => UnaryOp::Negate,
+                TokenType::Tilde => UnaryOp::BitNot,
                 _ => unreachable!(),
             };
             let right = Box::new(self.unary()?);
             return Ok(Expr::UnaryOp { operator, right });
         }
 
-        self.call()
+        self.power()
+    }
+
+    /// `-2 ** 2` is `-(2 ** 2)`, while the exponent may itself be negated
+    /// (`2 ** -1`) or another power (`2 ** 3 ** 2` is `2 ** 9`)
+    fn power(&mut self) -> Result<Expr, String> {
+        let expr = self.call()?;
+
+        if self.match_token(&[TokenType::StarStar]) {
+            let right = Box::new(self.unary()?);
+            return Ok(Expr::BinaryOp {
+                left: Box::new(expr),
+                operator: BinaryOp::Power,
+                right,
+            });
+        }
+
+        Ok(expr)
     }
 
     fn call(&mut self) -> Result<Expr, String> {
-        let mut expr = self.primary()?;
+        let expr = self.primary()?;
+        // Arguments and indexes are bracketed off from a guard's `=>`
+        self.outside_guard(|parser| parser.postfix(expr))
+    }
 
+    /// Parse with `parse` as if outside any match guard, so `=>` starts a lambda again
+    fn outside_guard<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        let outer = std::mem::replace(&mut self.in_guard, false);
+        let result = parse(self);
+        self.in_guard = outer;
+        result
+    }
+
+    /// The calls, indexes and member accesses following `expr`
+    fn postfix(&mut self, mut expr: Expr) -> Result<Expr, String> {
         loop {
             if self.match_token(&[TokenType::LeftParen]) {
                 expr = self.finish_call(expr)?;
-            } else if self.match_token(&[TokenType::Dot]) {
+            } else if self.check(&TokenType::LeftBracket) && self.peek().line == self.previous().line {
+                // A `[` on a new line starts a new statement, such as `[a, b] = pair`,
+                // rather than indexing the previous one
+                self.advance();
+                let index = self.expression()?;
+                self.consume(TokenType::RightBracket, "Expected ']' after index")?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                };
+            } else if self.match_token(&[TokenType::Dot, TokenType::QuestionDot]) {
+                let optional = self.previous().token_type == TokenType::QuestionDot;
                 if let TokenType::Identifier(name) = &self.peek().token_type {
                     let member_name = name.clone();
                     self.advance();
@@ -571,20 +906,25 @@ impl Parser {
                         }
                         
                         self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+                        if let Some(lambda) = self.trailing_lambda()? {
+                            args.push(lambda);
+                        }
                         expr = Expr::MethodCall {
                             object: Box::new(expr),
                             method: member_name,
                             args,
+                            optional,
                         };
                     } else {
                         // Property access
                         expr = Expr::PropertyAccess {
                             object: Box::new(expr),
                             property: member_name,
+                            optional,
                         };
                     }
                 } else {
-                    return Err(format!("Expected property or method name after '.' at line {}", self.peek().line));
+                    return Err(format!("Expected property or method name after '{}' at line {}", if optional { "?." } else { "." }, self.peek().line));
                 }
             } else {
                 break;
@@ -607,6 +947,9 @@ impl Parser {
         }
 
         self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+        if let Some(lambda) = self.trailing_lambda()? {
+            args.push(lambda);
+        }
 
         if let Expr::Variable(name) = callee {
             Ok(Expr::FunctionCall { name, args })
@@ -615,7 +958,66 @@ impl Parser {
         }
     }
 
+    /// The `key: value` entries of a map literal or field initializer, after the
+    /// opening `{` and through the closing `}`
+    fn map_entries(&mut self) -> Result<Vec<(String, Expr)>, String> {
+        let mut entries = Vec::new();
+
+        if !self.check(&TokenType::RightBrace) {
+            loop {
+                let key = match &self.peek().token_type {
+                    TokenType::Identifier(id) | TokenType::String(id) => id.clone(),
+                    _ => return Err(format!("Expected map key at line {}", self.peek().line)),
+                };
+                self.advance();
+                self.consume(TokenType::Colon, "Expected ':' after map key")?;
+                entries.push((key, self.expression()?));
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expected '}' after map entries")?;
+        Ok(entries)
+    }
+
+    /// Parse a trailing block argument written after a call's closing paren,
+    /// e.g. `map(items) { x => x * 2 }`, as a lambda. Only a `{` followed by
+    /// `params =>` starts one, so blocks of statements are left alone
+    fn trailing_lambda(&mut self) -> Result<Option<Expr>, String> {
+        if !self.check(&TokenType::LeftBrace) {
+            return Ok(None);
+        }
+
+        let mut params = Vec::new();
+        let mut offset = 1;
+        loop {
+            match self.tokens.get(self.current + offset).map(|t| &t.token_type) {
+                Some(TokenType::Identifier(name)) => params.push(name.clone()),
+                _ => return Ok(None),
+            }
+            match self.tokens.get(self.current + offset + 1).map(|t| &t.token_type) {
+                Some(TokenType::Comma) => offset += 2,
+                Some(TokenType::Arrow) => break,
+                _ => return Ok(None),
+            }
+        }
+
+        self.current += offset + 2; // Skip '{', the parameters, and '=>'
+        let body = Box::new(self.expression()?);
+        self.consume(TokenType::RightBrace, "Expected '}' after trailing lambda body")?;
+        Ok(Some(Expr::Lambda { params, body }))
+    }
+
     fn primary(&mut self) -> Result<Expr, String> {
+        // Only a lambda at the top level of a guard would swallow the guard's
+        // `=>`; whatever is nested inside brackets parses as usual
+        let in_guard = self.in_guard;
+        self.outside_guard(|parser| parser.primary_expr(in_guard))
+    }
+
+    fn primary_expr(&mut self, in_guard: bool) -> Result<Expr, String> {
         match &self.peek().token_type {
             TokenType::True => {
                 self.advance();
@@ -630,7 +1032,7 @@ impl Parser {
                 Ok(Expr::Literal(Literal::Null))
             }
             TokenType::Number(n) => {
-                let num = n.parse::<f64>().map_err(|_| "Invalid number")?;
+                let num = parse_number(n, self.peek().line)?;
                 self.advance();
                 Ok(Expr::Literal(Literal::Number(num)))
             }
@@ -639,13 +1041,37 @@ impl Parser {
                 self.advance();
                 Ok(Expr::Literal(Literal::String(str)))
             }
+            TokenType::TemplateString(parts) => {
+                let parts = parts.clone();
+                let line = self.peek().line;
+                self.advance();
+
+                let mut exprs = Vec::new();
+                for part in parts {
+                    match part {
+                        TemplatePart::Literal(s) => exprs.push(Expr::Literal(Literal::String(s))),
+                        TemplatePart::Code(source) => exprs.push(parse_interpolation(&source, line)?),
+                    }
+                }
+                Ok(Expr::Template(exprs))
+            }
             TokenType::New => {
                 self.advance();
                 if let TokenType::Identifier(class_name) = &self.peek().token_type {
                     let name = class_name.clone();
                     self.advance();
                     
-                    self.consume(TokenType::LeftParen, "Expected '(' after class name")?;
+                    // `new Point { x: 1, y: 2 }` sets fields instead of calling the constructor
+                    if self.match_token(&[TokenType::LeftBrace]) {
+                        let fields = self.map_entries()?;
+                        return Ok(Expr::New {
+                            class_name: name,
+                            args: Vec::new(),
+                            fields: Some(fields),
+                        });
+                    }
+
+                    self.consume(TokenType::LeftParen, "Expected '(' or '{' after class name")?;
                     
                     let mut args = Vec::new();
                     if !self.check(&TokenType::RightParen) {
@@ -662,33 +1088,94 @@ impl Parser {
                     Ok(Expr::New {
                         class_name: name,
                         args,
+                        fields: None,
                     })
                 } else {
                     Err(format!("Expected class name after 'new' at line {}", self.peek().line))
                 }
             }
+            TokenType::Func => {
+                self.advance();
+                self.consume(TokenType::LeftParen, "Expected '(' after 'func' in function expression")?;
+                let params = self.parameters()?;
+                self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
+                self.consume(TokenType::LeftBrace, "Expected '{' before function body")?;
+
+                let mut body = Vec::new();
+                while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+                    body.push(self.declaration()?);
+                }
+
+                self.consume(TokenType::RightBrace, "Expected '}' after function body")?;
+                Ok(Expr::Function { params, body })
+            }
+            TokenType::Super => {
+                let line = self.peek().line;
+                self.advance();
+
+                let method = if self.match_token(&[TokenType::Dot]) {
+                    match &self.peek().token_type {
+                        TokenType::Identifier(name) => {
+                            let name = name.clone();
+                            self.advance();
+                            Some(name)
+                        }
+                        _ => return Err(format!("Expected method name after 'super.' at line {}", line)),
+                    }
+                } else {
+                    None
+                };
+                if !self.match_token(&[TokenType::LeftParen]) {
+                    return Err(format!("Expected '(' after 'super' call at line {}", line));
+                }
+
+                let mut args = Vec::new();
+                if !self.check(&TokenType::RightParen) {
+                    loop {
+                        args.push(self.expression()?);
+                        if !self.match_token(&[TokenType::Comma]) {
+                            break;
+                        }
+                    }
+                }
+
+                self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+                Ok(Expr::SuperCall { method, args })
+            }
             TokenType::Identifier(id) => {
                 let name = id.clone();
                 self.advance();
+
+                // `x => body` is a one-parameter lambda
+                if !in_guard && self.match_token(&[TokenType::Arrow]) {
+                    let body = Box::new(self.expression()?);
+                    return Ok(Expr::Lambda { params: vec![name], body });
+                }
                 Ok(Expr::Variable(name))
             }
             TokenType::LeftParen => {
                 self.advance();
                 
+                // `() => body` takes no parameters; in a guard, `(…) =>` is a
+                // parenthesized guard followed by the case body
+                if !in_guard && self.check(&TokenType::RightParen) {
+                    self.advance();
+                    self.consume(TokenType::Arrow, "Expected '=>' after '()'")?;
+                    let body = Box::new(self.expression()?);
+                    return Ok(Expr::Lambda { params: Vec::new(), body });
+                }
+
                 // Check for lambda: (params) => body
-                if let TokenType::Identifier(_) = &self.peek().token_type {
+                if !in_guard && matches!(self.peek().token_type, TokenType::Identifier(_)) {
                     let start_pos = self.current;
                     let mut params = Vec::new();
                     
                     // Try to parse as lambda parameters
-                    loop {
-                        if let TokenType::Identifier(id) = &self.peek().token_type {
-                            params.push(id.clone());
-                            self.advance();
-                        } else {
-                            break;
-                        }
-                        
+                    while let TokenType::Identifier(id) = &self.peek().token_type {
+                        params.push(id.clone());
+                        self.advance();
+
                         if !self.match_token(&[TokenType::Comma]) {
                             break;
                         }
@@ -703,8 +1190,8 @@ impl Parser {
                         }
                     }
                     
-                    // Not a lambda, backtrack
-                    self.current = start_pos - 1; // -1 because we already consumed LeftParen
+                    // Not a lambda, backtrack to just after the LeftParen
+                    self.current = start_pos;
                 }
                 
                 // Regular grouped expression
@@ -718,7 +1205,11 @@ impl Parser {
                 
                 if !self.check(&TokenType::RightBracket) {
                     loop {
-                        elements.push(self.expression()?);
+                        if self.match_token(&[TokenType::Ellipsis]) {
+                            elements.push(Expr::Spread(Box::new(self.expression()?)));
+                        } else {
+                            elements.push(self.expression()?);
+                        }
                         if !self.match_token(&[TokenType::Comma]) {
                             break;
                         }
@@ -728,6 +1219,10 @@ impl Parser {
                 self.consume(TokenType::RightBracket, "Expected ']' after array elements")?;
                 Ok(Expr::Array(elements))
             }
+            TokenType::LeftBrace => {
+                self.advance();
+                Ok(Expr::Map(self.map_entries()?))
+            }
             TokenType::Match => {
                 self.advance();
                 self.consume(TokenType::LeftParen, "Expected '(' after 'match'")?;
@@ -736,12 +1231,36 @@ impl Parser {
                 self.consume(TokenType::LeftBrace, "Expected '{' before match cases")?;
                 
                 let mut cases = Vec::new();
+                // Cases without a body fall through to the next case's body
+                let mut bodyless: Vec<(Pattern, Option<Expr>)> = Vec::new();
                 
                 while self.match_token(&[TokenType::Case]) {
-                    let pattern = self.match_pattern()?;
+                    let mut pattern = self.match_pattern()?;
+                    if self.check(&TokenType::Comma) {
+                        let mut alternatives = vec![pattern];
+                        while self.match_token(&[TokenType::Comma]) {
+                            alternatives.push(self.match_pattern()?);
+                        }
+                        pattern = Pattern::Or(alternatives);
+                    }
+                    let guard = if self.match_token(&[TokenType::If]) {
+                        let outer = std::mem::replace(&mut self.in_guard, true);
+                        let guard = self.expression();
+                        self.in_guard = outer;
+                        Some(guard?)
+                    } else {
+                        None
+                    };
+                    if self.check(&TokenType::Case) {
+                        bodyless.push((pattern, guard));
+                        continue;
+                    }
                     self.consume(TokenType::Arrow, "Expected '=>' after case pattern")?;
                     let body = self.expression()?;
-                    cases.push(MatchCase { pattern, body });
+                    for (pattern, guard) in bodyless.drain(..) {
+                        cases.push(MatchCase { pattern, guard, body: body.clone() });
+                    }
+                    cases.push(MatchCase { pattern, guard, body });
                 }
                 
                 self.consume(TokenType::RightBrace, "Expected '}' after match cases")?;
@@ -762,7 +1281,7 @@ impl Parser {
                 Ok(Pattern::Literal(Literal::String(str)))
             }
             TokenType::Number(n) => {
-                let num = n.parse::<f64>().map_err(|_| "Invalid number")?;
+                let num = parse_number(n, self.peek().line)?;
                 self.advance();
                 Ok(Pattern::Literal(Literal::Number(num)))
             }
@@ -787,3 +1306,235 @@ impl Parser {
         }
     }
 }
+
+/// Parse the source of a `${...}` interpolation as a single expression
+fn parse_interpolation(source: &str, line: usize) -> Result<Expr, String> {
+    let mut lexer = Lexer::new(source.to_string());
+    let tokens = lexer
+        .tokenize()
+        .map_err(|err| format!("{} in string interpolation at line {}", err, line))?;
+    let mut parser = Parser::new(tokens);
+    let expr = parser
+        .expression()
+        .map_err(|err| format!("{} in string interpolation at line {}", err, line))?;
+    if !parser.is_at_end() {
+        return Err(format!("Unexpected token {:?} in string interpolation at line {}", parser.peek().token_type, line));
+    }
+    Ok(expr)
+}
+
+/// Decode the text of a number token: `_` separators are stripped and a
+/// `0x`/`0o`/`0b` prefix selects an integer radix
+fn parse_number(text: &str, line: usize) -> Result<f64, String> {
+    let digits: String = text.chars().filter(|c| *c != '_').collect();
+    let radix = match digits.get(..2) {
+        Some("0x") | Some("0X") => 16,
+        Some("0o") | Some("0O") => 8,
+        Some("0b") | Some("0B") => 2,
+        _ => 10,
+    };
+
+    let parsed = if radix == 10 {
+        digits.parse::<f64>().ok()
+    } else {
+        i64::from_str_radix(&digits[2..], radix).ok().map(|n| n as f64)
+    };
+    parsed.ok_or_else(|| format!("Invalid number '{}' at line {}", text, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_tokens(token_types: Vec<TokenType>) -> Result<Program, String> {
+        let mut tokens: Vec<Token> = token_types
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| Token::new(t, 1, i + 1))
+            .collect();
+        tokens.push(Token::new(TokenType::Eof, 1, tokens.len() + 1));
+        Parser::new(tokens).parse()
+    }
+
+    fn number_literal(text: &str) -> Expr {
+        let program = parse_tokens(vec![TokenType::Number(text.to_string())]).unwrap();
+        match &program.statements[0].kind {
+            StmtKind::Expr(expr) => expr.clone(),
+            other => panic!("Expected expression statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_number_literal_forms() {
+        assert_eq!(number_literal("42"), Expr::Literal(Literal::Number(42.0)));
+        assert_eq!(number_literal("2.5"), Expr::Literal(Literal::Number(2.5)));
+        assert_eq!(number_literal("1_000_000"), Expr::Literal(Literal::Number(1_000_000.0)));
+        assert_eq!(number_literal("0xFF"), Expr::Literal(Literal::Number(255.0)));
+        assert_eq!(number_literal("0o17"), Expr::Literal(Literal::Number(15.0)));
+        assert_eq!(number_literal("0b1010"), Expr::Literal(Literal::Number(10.0)));
+        assert_eq!(number_literal("0xff_ff"), Expr::Literal(Literal::Number(65535.0)));
+    }
+
+    #[test]
+    fn test_number_pattern_decoded() {
+        let program = parse_tokens(vec![
+            TokenType::Match,
+            TokenType::LeftParen,
+            TokenType::Identifier("x".to_string()),
+            TokenType::RightParen,
+            TokenType::LeftBrace,
+            TokenType::Case,
+            TokenType::Number("0b11".to_string()),
+            TokenType::Arrow,
+            TokenType::True,
+            TokenType::RightBrace,
+        ])
+        .unwrap();
+        match &program.statements[0].kind {
+            StmtKind::Expr(Expr::Match { cases, .. }) => {
+                assert_eq!(cases[0].pattern, Pattern::Literal(Literal::Number(3.0)));
+            }
+            other => panic!("Expected match expression, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_hex_literal_from_source() {
+        let mut lexer = crate::lexer::Lexer::new("0xFF".to_string());
+        let program = Parser::new(lexer.tokenize().unwrap()).parse().unwrap();
+        assert_eq!(
+            program.statements[0].kind,
+            StmtKind::Expr(Expr::Literal(Literal::Number(255.0)))
+        );
+    }
+
+    #[test]
+    fn test_exponent_and_separator_from_source() {
+        let mut lexer = crate::lexer::Lexer::new("2.5e-3 1_000_000".to_string());
+        let program = Parser::new(lexer.tokenize().unwrap()).parse().unwrap();
+        assert_eq!(
+            program.statements[0].kind,
+            StmtKind::Expr(Expr::Literal(Literal::Number(0.0025)))
+        );
+        assert_eq!(
+            program.statements[1].kind,
+            StmtKind::Expr(Expr::Literal(Literal::Number(1_000_000.0)))
+        );
+    }
+
+    #[test]
+    fn test_invalid_number_literal() {
+        assert!(parse_tokens(vec![TokenType::Number("0xZZ".to_string())]).is_err());
+    }
+
+    #[test]
+    fn test_required_parameter_after_default() {
+        let mut lexer = crate::lexer::Lexer::new("func f(a = 1, b) { }".to_string());
+        let err = Parser::new(lexer.tokenize().unwrap()).parse().unwrap_err();
+        assert_eq!(err, "Parameter 'b' needs a default value because an earlier parameter has one at line 1");
+    }
+
+    #[test]
+    fn test_rest_parameter_must_be_last() {
+        let mut lexer = crate::lexer::Lexer::new("func f(...a, b) { }".to_string());
+        let err = Parser::new(lexer.tokenize().unwrap()).parse().unwrap_err();
+        assert_eq!(err, "Rest parameter must be the last parameter at line 1");
+    }
+
+    #[test]
+    fn test_const_method_rejected() {
+        let mut lexer = crate::lexer::Lexer::new("class A {\n  const func f() { }\n}".to_string());
+        let err = Parser::new(lexer.tokenize().unwrap()).parse().unwrap_err();
+        assert_eq!(err, "Methods can't be 'const' at line 2");
+    }
+
+    #[test]
+    fn test_last_match_case_needs_body() {
+        let mut lexer = crate::lexer::Lexer::new("x = match (1) {\n  case 1\n  case 2\n}".to_string());
+        let err = Parser::new(lexer.tokenize().unwrap()).parse().unwrap_err();
+        assert_eq!(err, "Expected '=>' after case pattern at line 4, column 1");
+    }
+
+    #[test]
+    fn test_parenthesized_expressions_and_lambdas() {
+        let parse = |source: &str| {
+            let mut lexer = crate::lexer::Lexer::new(source.to_string());
+            match Parser::new(lexer.tokenize().unwrap()).parse().unwrap().statements.remove(0).kind {
+                StmtKind::Expr(expr) => expr,
+                other => panic!("Expected expression statement, got {:?}", other),
+            }
+        };
+        let variable = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        assert_eq!(
+            parse("(a + b) * c"),
+            Expr::BinaryOp {
+                left: Box::new(Expr::BinaryOp { left: variable("a"), operator: BinaryOp::Add, right: variable("b") }),
+                operator: BinaryOp::Multiply,
+                right: variable("c"),
+            }
+        );
+        assert_eq!(
+            parse("-a ** b"),
+            Expr::UnaryOp {
+                operator: UnaryOp::Negate,
+                right: Box::new(Expr::BinaryOp { left: variable("a"), operator: BinaryOp::Power, right: variable("b") }),
+            }
+        );
+        assert_eq!(
+            parse("a ** b ** c"),
+            Expr::BinaryOp {
+                left: variable("a"),
+                operator: BinaryOp::Power,
+                right: Box::new(Expr::BinaryOp { left: variable("b"), operator: BinaryOp::Power, right: variable("c") }),
+            }
+        );
+        assert_eq!(parse("() => a"), Expr::Lambda { params: Vec::new(), body: variable("a") });
+        assert_eq!(parse("(a) => a"), Expr::Lambda { params: vec!["a".to_string()], body: variable("a") });
+    }
+
+    #[test]
+    fn test_bare_parameter_lambda() {
+        let parse = |source: &str| {
+            let mut lexer = crate::lexer::Lexer::new(source.to_string());
+            Parser::new(lexer.tokenize().unwrap()).parse().unwrap().statements.remove(0).kind
+        };
+        let variable = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        assert_eq!(
+            parse("x => x + 1"),
+            StmtKind::Expr(Expr::Lambda {
+                params: vec!["x".to_string()],
+                body: Box::new(Expr::BinaryOp {
+                    left: variable("x"),
+                    operator: BinaryOp::Add,
+                    right: Box::new(Expr::Literal(Literal::Number(1.0))),
+                }),
+            })
+        );
+        assert_eq!(parse("x"), StmtKind::Expr(Expr::Variable("x".to_string())));
+
+        // In a guard, `=>` ends the guard instead of making a lambda
+        match parse("match (1) { case n if ready => n }") {
+            StmtKind::Expr(Expr::Match { cases, .. }) => {
+                assert_eq!(cases[0].guard, Some(Expr::Variable("ready".to_string())));
+                assert_eq!(cases[0].body, Expr::Variable("n".to_string()));
+            }
+            other => panic!("Expected match expression, got {:?}", other),
+        }
+
+        // Inside the guard's arguments and brackets `=>` makes a lambda again
+        let lambda = Expr::Lambda {
+            params: vec!["x".to_string()],
+            body: Box::new(Expr::BinaryOp { left: variable("x"), operator: BinaryOp::Greater, right: variable("n") }),
+        };
+        match parse("match (1) { case n if any(xs, x => x > n) => \"big\" }") {
+            StmtKind::Expr(Expr::Match { cases, .. }) => {
+                assert_eq!(
+                    cases[0].guard,
+                    Some(Expr::FunctionCall { name: "any".to_string(), args: vec![*variable("xs"), lambda] })
+                );
+                assert_eq!(cases[0].body, Expr::Literal(Literal::String("big".to_string())));
+            }
+            other => panic!("Expected match expression, got {:?}", other),
+        }
+    }
+}
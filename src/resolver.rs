@@ -0,0 +1,333 @@
+//! A static pass run between parsing and execution: resolves every variable
+//! read to the lexical scope that declares it, and reports undefined variables
+//! and uses before declaration without running the program.
+//!
+//! Scopes mirror the ones the interpreter pushes (blocks, loops, function and
+//! method calls, match cases). Code inside functions may read any top-level
+//! name, because globals are looked up when the function runs; code at the top
+//! level may only read names declared above it.
+
+use crate::parser::ast::*;
+use crate::runtime::value::TYPE_NAMES;
+use std::collections::{HashMap, HashSet};
+
+/// Where one variable read resolved to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution {
+    pub name: String,
+    pub line: usize,
+    pub depth: Option<usize>, // scopes out from the innermost one, or None for a global
+}
+
+pub struct Resolver {
+    scopes: Vec<HashSet<String>>,
+    globals: HashSet<String>,   // defined so far, including builtins and hoisted functions
+    top_level: HashSet<String>, // declared anywhere at the top level of the program
+    classes: HashMap<String, HashSet<String>>, // class name -> property names, inherited ones included
+    function_depth: usize,
+    line: usize,
+    resolutions: Vec<Resolution>,
+    errors: Vec<String>,
+}
+
+impl Resolver {
+    /// `globals` are the names already defined when the program starts, such as
+    /// the builtins
+    pub fn new(globals: impl IntoIterator<Item = String>) -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            globals: globals.into_iter().collect(),
+            top_level: HashSet::new(),
+            classes: HashMap::new(),
+            function_depth: 0,
+            line: 0,
+            resolutions: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Resolve every variable read in `program`, in source order, or return
+    /// every error found
+    pub fn resolve(mut self, program: &Program) -> Result<Vec<Resolution>, Vec<String>> {
+        for stmt in &program.statements {
+            match &stmt.kind {
+                StmtKind::FuncDecl { name, .. } => {
+                    // Hoisted, like the interpreter does
+                    self.globals.insert(name.clone());
+                    self.top_level.insert(name.clone());
+                }
+                StmtKind::VarDecl { name, .. } | StmtKind::Let { name, .. } | StmtKind::ClassDecl { name, .. } => {
+                    self.top_level.insert(name.clone());
+                }
+                StmtKind::Destructure { names, rest, .. } => {
+                    self.top_level.extend(names.iter().chain(rest).cloned());
+                }
+                _ => {}
+            }
+        }
+
+        walk_program(&mut self, program);
+        if self.errors.is_empty() {
+            Ok(self.resolutions)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn lookup(&mut self, name: &str) {
+        let depth = self.scopes.iter().rev().position(|scope| scope.contains(name));
+        if depth.is_some() || self.globals.contains(name) || (self.function_depth > 0 && self.top_level.contains(name)) {
+            self.resolutions.push(Resolution { name: name.to_string(), line: self.line, depth });
+        } else if self.top_level.contains(name) {
+            self.errors.push(format!("Variable '{}' used before its declaration at line {}", name, self.line));
+        } else {
+            self.errors.push(format!("Undefined variable: {} at line {}", name, self.line));
+        }
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+            || self.globals.contains(name)
+            || (self.function_depth > 0 && self.top_level.contains(name))
+    }
+
+    fn declare(&mut self, name: &str) {
+        match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.to_string()),
+            None => self.globals.insert(name.to_string()),
+        };
+    }
+
+    /// Plain `name = value` updates a visible variable or declares one in the
+    /// innermost scope
+    fn assign(&mut self, name: &str) {
+        if !self.is_defined(name) {
+            self.declare(name);
+        }
+    }
+
+    /// Parameters share one scope with the body; defaults may read earlier parameters
+    fn function(&mut self, params: &[Param], body: &[Stmt]) {
+        self.function_depth += 1;
+        self.scopes.push(HashSet::new());
+        for param in params {
+            if let Some(default) = &param.default {
+                self.visit_expr(default);
+            }
+            self.declare(&param.name);
+        }
+        for stmt in body {
+            self.visit_stmt(stmt);
+        }
+        self.scopes.pop();
+        self.function_depth -= 1;
+    }
+
+    fn class(&mut self, name: &str, extends: &Option<String>, methods: &[MethodDecl], properties: &[PropertyDecl]) {
+        if let Some(parent) = extends {
+            self.lookup(parent);
+        }
+        for property in properties {
+            self.visit_expr(&property.value);
+        }
+        self.declare(name);
+
+        let mut fields: HashSet<String> = extends
+            .as_ref()
+            .and_then(|parent| self.classes.get(parent))
+            .cloned()
+            .unwrap_or_default();
+        fields.extend(properties.iter().map(|p| p.name.clone()));
+        self.classes.insert(name.to_string(), fields.clone());
+
+        // Methods see `this` and the properties in a receiver scope below their own
+        fields.insert("this".to_string());
+        self.scopes.push(fields);
+        for method in methods {
+            self.function(&method.params, &method.body);
+        }
+        self.scopes.pop();
+    }
+}
+
+impl Visitor for Resolver {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.line = stmt.line;
+        match &stmt.kind {
+            StmtKind::VarDecl { name, value } => {
+                self.visit_expr(value);
+                self.assign(name);
+            }
+            StmtKind::Let { name, value } => {
+                self.visit_expr(value);
+                self.declare(name);
+            }
+            StmtKind::Destructure { names, rest, value } => {
+                self.visit_expr(value);
+                for name in names.iter().chain(rest) {
+                    self.assign(name);
+                }
+            }
+            StmtKind::FuncDecl { name, params, body, .. } => {
+                self.declare(name);
+                self.function(params, body);
+            }
+            StmtKind::For { init, condition, increment, body } => {
+                if let Some(init) = init {
+                    self.visit_stmt(init);
+                }
+                // Each iteration runs on a copy of the loop variables, written back
+                // to the enclosing scope before the increment
+                let loop_vars = match init.as_ref().map(|stmt| &stmt.kind) {
+                    Some(StmtKind::VarDecl { name, .. } | StmtKind::Let { name, .. }) => HashSet::from([name.clone()]),
+                    Some(StmtKind::Destructure { names, rest, .. }) => names.iter().chain(rest).cloned().collect(),
+                    _ => HashSet::new(),
+                };
+                self.scopes.push(loop_vars);
+                if let Some(condition) = condition {
+                    self.visit_expr(condition);
+                }
+                self.visit_stmt(body);
+                self.scopes.pop();
+                if let Some(increment) = increment {
+                    self.visit_expr(increment);
+                }
+            }
+            StmtKind::ForEach { index, variable, iterable, body } => {
+                self.visit_expr(iterable);
+                self.scopes.push(index.iter().chain([variable]).cloned().collect());
+                self.visit_stmt(body);
+                self.scopes.pop();
+            }
+            StmtKind::ClassDecl { name, extends, methods, properties } => {
+                self.class(name, extends, methods, properties);
+            }
+            StmtKind::Block(stmts) => {
+                self.scopes.push(HashSet::new());
+                for stmt in stmts {
+                    self.visit_stmt(stmt);
+                }
+                self.scopes.pop();
+            }
+            _ => walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable(name) => self.lookup(name),
+            Expr::FunctionCall { name, .. } => {
+                self.lookup(name);
+                walk_expr(self, expr);
+            }
+            Expr::New { class_name, .. } => {
+                self.lookup(class_name);
+                walk_expr(self, expr);
+            }
+            Expr::Assign { name, value } => {
+                self.visit_expr(value);
+                // An assignment expression to an unknown name defines a global
+                if !self.is_defined(name) {
+                    self.globals.insert(name.clone());
+                }
+            }
+            Expr::Lambda { params, body } => {
+                self.function_depth += 1;
+                self.scopes.push(params.iter().cloned().collect());
+                self.visit_expr(body);
+                self.scopes.pop();
+                self.function_depth -= 1;
+            }
+            Expr::Function { params, body } => self.function(params, body),
+            Expr::Match { expr, cases } => {
+                self.visit_expr(expr);
+                for case in cases {
+                    self.scopes.push(HashSet::new());
+                    if let Pattern::Identifier(name) = &case.pattern {
+                        if !TYPE_NAMES.contains(&name.as_str()) {
+                            self.declare(name);
+                        }
+                    }
+                    if let Some(guard) = &case.guard {
+                        self.visit_expr(guard);
+                    }
+                    self.visit_expr(&case.body);
+                    self.scopes.pop();
+                }
+            }
+            _ => walk_expr(self, expr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn resolve(source: &str) -> Result<Vec<Resolution>, Vec<String>> {
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        Resolver::new(["print".to_string()]).resolve(&program)
+    }
+
+    fn depths(source: &str, name: &str) -> Vec<Option<usize>> {
+        resolve(source)
+            .unwrap()
+            .into_iter()
+            .filter(|r| r.name == name)
+            .map(|r| r.depth)
+            .collect()
+    }
+
+    #[test]
+    fn test_forward_reference() {
+        assert_eq!(
+            resolve("print(total)\ntotal = 1").unwrap_err(),
+            vec!["Variable 'total' used before its declaration at line 1"]
+        );
+        assert_eq!(
+            resolve("x = 1\nif (x) {\n  print(y)\n}").unwrap_err(),
+            vec!["Undefined variable: y at line 3"]
+        );
+        // Function bodies read globals when called, and functions are hoisted
+        assert!(resolve("print(f())\nfunc f() { return later }\nlater = 1").is_ok());
+    }
+
+    #[test]
+    fn test_shadowed_names() {
+        let source = "x = 1
+                      {
+                          let x = 2
+                          {
+                              print(x)
+                          }
+                      }
+                      print(x)
+                      func f(x) { return [x] }
+                      g = (y) => x + y";
+        assert_eq!(depths(source, "x"), vec![Some(1), None, Some(0), None]);
+    }
+
+    #[test]
+    fn test_scopes_end_with_their_block() {
+        assert_eq!(
+            resolve("for (i = 0; i < 3; i += 1) { let sq = i * i }\nprint(i)\nprint(sq)").unwrap_err(),
+            vec!["Undefined variable: sq at line 3"]
+        );
+        let source = "class Counter {
+                          count = 0
+                          func bump(by = 1) {
+                              count = count + by
+                              return this
+                          }
+                      }
+                      total = match (3) { case n if n > 2 => n * 2 }
+                      for (i, item in [1]) { print(i + item) }";
+        assert_eq!(depths(source, "count"), vec![Some(1)]);
+        assert_eq!(depths(source, "by"), vec![Some(0)]);
+        assert_eq!(depths(source, "n"), vec![Some(0), Some(0)]);
+        assert_eq!(depths(source, "item"), vec![Some(1)]);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::runtime::value::Value;
+use crate::runtime::value::{Arity, Value};
 
 pub fn register_builtins() -> std::collections::HashMap<String, Value> {
     let mut builtins = std::collections::HashMap::new();
@@ -7,7 +7,7 @@ pub fn register_builtins() -> std::collections::HashMap<String, Value> {
         "typeof".to_string(),
         Value::NativeFunction {
             name: "typeof".to_string(),
-            arity: 1,
+            arity: Arity::Exact(1),
         },
     );
 
@@ -15,7 +15,7 @@ pub fn register_builtins() -> std::collections::HashMap<String, Value> {
         "print".to_string(),
         Value::NativeFunction {
             name: "print".to_string(),
-            arity: 1,
+            arity: Arity::AtLeast(0),
         },
     );
 
@@ -23,7 +23,7 @@ pub fn register_builtins() -> std::collections::HashMap<String, Value> {
         "map".to_string(),
         Value::NativeFunction {
             name: "map".to_string(),
-            arity: 2,
+            arity: Arity::Exact(2),
         },
     );
 
@@ -31,7 +31,15 @@ pub fn register_builtins() -> std::collections::HashMap<String, Value> {
         "filter".to_string(),
         Value::NativeFunction {
             name: "filter".to_string(),
-            arity: 2,
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "forEach".to_string(),
+        Value::NativeFunction {
+            name: "forEach".to_string(),
+            arity: Arity::Exact(2),
         },
     );
 
@@ -39,13 +47,635 @@ pub fn register_builtins() -> std::collections::HashMap<String, Value> {
         "len".to_string(),
         Value::NativeFunction {
             name: "len".to_string(),
-            arity: 1,
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "round".to_string(),
+        Value::NativeFunction {
+            name: "round".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "round_half_up".to_string(),
+        Value::NativeFunction {
+            name: "round_half_up".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "round_half_even".to_string(),
+        Value::NativeFunction {
+            name: "round_half_even".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "trunc".to_string(),
+        Value::NativeFunction {
+            name: "trunc".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "with".to_string(),
+        Value::NativeFunction {
+            name: "with".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "reduce".to_string(),
+        Value::NativeFunction {
+            name: "reduce".to_string(),
+            arity: Arity::Range(2, 3),
+        },
+    );
+
+    builtins.insert(
+        "sort".to_string(),
+        Value::NativeFunction {
+            name: "sort".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "push".to_string(),
+        Value::NativeFunction {
+            name: "push".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "pop".to_string(),
+        Value::NativeFunction {
+            name: "pop".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "join".to_string(),
+        Value::NativeFunction {
+            name: "join".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "contains".to_string(),
+        Value::NativeFunction {
+            name: "contains".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "index_of".to_string(),
+        Value::NativeFunction {
+            name: "index_of".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "reverse".to_string(),
+        Value::NativeFunction {
+            name: "reverse".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "slice".to_string(),
+        Value::NativeFunction {
+            name: "slice".to_string(),
+            arity: Arity::Range(2, 3),
+        },
+    );
+
+    builtins.insert(
+        "to_json".to_string(),
+        Value::NativeFunction {
+            name: "to_json".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "band".to_string(),
+        Value::NativeFunction {
+            name: "band".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "bor".to_string(),
+        Value::NativeFunction {
+            name: "bor".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "bxor".to_string(),
+        Value::NativeFunction {
+            name: "bxor".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "bnot".to_string(),
+        Value::NativeFunction {
+            name: "bnot".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "shl".to_string(),
+        Value::NativeFunction {
+            name: "shl".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "shr".to_string(),
+        Value::NativeFunction {
+            name: "shr".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "loose_eq".to_string(),
+        Value::NativeFunction {
+            name: "loose_eq".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "find".to_string(),
+        Value::NativeFunction {
+            name: "find".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "find_index".to_string(),
+        Value::NativeFunction {
+            name: "find_index".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "to_string".to_string(),
+        Value::NativeFunction {
+            name: "to_string".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "keys".to_string(),
+        Value::NativeFunction {
+            name: "keys".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "own_keys".to_string(),
+        Value::NativeFunction {
+            name: "own_keys".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "num".to_string(),
+        Value::NativeFunction {
+            name: "num".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "try_num".to_string(),
+        Value::NativeFunction {
+            name: "try_num".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "any".to_string(),
+        Value::NativeFunction {
+            name: "any".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "all".to_string(),
+        Value::NativeFunction {
+            name: "all".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "values".to_string(),
+        Value::NativeFunction {
+            name: "values".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "entries".to_string(),
+        Value::NativeFunction {
+            name: "entries".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "has".to_string(),
+        Value::NativeFunction {
+            name: "has".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "wrap".to_string(),
+        Value::NativeFunction {
+            name: "wrap".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "clamp".to_string(),
+        Value::NativeFunction {
+            name: "clamp".to_string(),
+            arity: Arity::Exact(3),
+        },
+    );
+
+    builtins.insert(
+        "from_json".to_string(),
+        Value::NativeFunction {
+            name: "from_json".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "chunk".to_string(),
+        Value::NativeFunction {
+            name: "chunk".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "format_number".to_string(),
+        Value::NativeFunction {
+            name: "format_number".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "assert".to_string(),
+        Value::NativeFunction {
+            name: "assert".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "lazy".to_string(),
+        Value::NativeFunction {
+            name: "lazy".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "force".to_string(),
+        Value::NativeFunction {
+            name: "force".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "zip_with".to_string(),
+        Value::NativeFunction {
+            name: "zip_with".to_string(),
+            arity: Arity::Exact(3),
+        },
+    );
+
+    builtins.insert(
+        "methods".to_string(),
+        Value::NativeFunction {
+            name: "methods".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "properties".to_string(),
+        Value::NativeFunction {
+            name: "properties".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "sum".to_string(),
+        Value::NativeFunction {
+            name: "sum".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "min".to_string(),
+        Value::NativeFunction {
+            name: "min".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "max".to_string(),
+        Value::NativeFunction {
+            name: "max".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "avg".to_string(),
+        Value::NativeFunction {
+            name: "avg".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "merge".to_string(),
+        Value::NativeFunction {
+            name: "merge".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "matches_glob".to_string(),
+        Value::NativeFunction {
+            name: "matches_glob".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "contains_all".to_string(),
+        Value::NativeFunction {
+            name: "contains_all".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "rotate".to_string(),
+        Value::NativeFunction {
+            name: "rotate".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "shuffle".to_string(),
+        Value::NativeFunction {
+            name: "shuffle".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "random".to_string(),
+        Value::NativeFunction {
+            name: "random".to_string(),
+            arity: Arity::Exact(0),
+        },
+    );
+
+    builtins.insert(
+        "random_int".to_string(),
+        Value::NativeFunction {
+            name: "random_int".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "seed".to_string(),
+        Value::NativeFunction {
+            name: "seed".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "help".to_string(),
+        Value::NativeFunction {
+            name: "help".to_string(),
+            arity: Arity::Range(0, 1),
+        },
+    );
+
+    builtins.insert(
+        "sort_by_key".to_string(),
+        Value::NativeFunction {
+            name: "sort_by_key".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "repr".to_string(),
+        Value::NativeFunction {
+            name: "repr".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "print_depth".to_string(),
+        Value::NativeFunction {
+            name: "print_depth".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "parse_grouped".to_string(),
+        Value::NativeFunction {
+            name: "parse_grouped".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "println".to_string(),
+        Value::NativeFunction {
+            name: "println".to_string(),
+            arity: Arity::AtLeast(0),
+        },
+    );
+
+    builtins.insert(
+        "input".to_string(),
+        Value::NativeFunction {
+            name: "input".to_string(),
+            arity: Arity::Range(0, 1),
+        },
+    );
+
+    builtins.insert(
+        "flatten".to_string(),
+        Value::NativeFunction {
+            name: "flatten".to_string(),
+            arity: Arity::Range(1, 2),
+        },
+    );
+
+    builtins.insert(
+        "flatten_deep".to_string(),
+        Value::NativeFunction {
+            name: "flatten_deep".to_string(),
+            arity: Arity::Exact(1),
+        },
+    );
+
+    builtins.insert(
+        "take_while".to_string(),
+        Value::NativeFunction {
+            name: "take_while".to_string(),
+            arity: Arity::Exact(2),
+        },
+    );
+
+    builtins.insert(
+        "drop_while".to_string(),
+        Value::NativeFunction {
+            name: "drop_while".to_string(),
+            arity: Arity::Exact(2),
         },
     );
 
     builtins
 }
 
+/// One-line descriptions of the builtins, shown by `help`
+const DESCRIPTIONS: &[(&str, &str)] = &[
+    ("typeof", "Name of a value's type"),
+    ("print", "Print values to stdout, separated by spaces"),
+    ("println", "Same as print"),
+    ("take_while", "Leading elements for which the predicate is truthy, up to the first that fails"),
+    ("drop_while", "The elements left after removing the leading run take_while returns"),
+    ("flatten", "Splice nested arrays into their parent, up to an optional depth (default 1)"),
+    ("flatten_deep", "Splice nested arrays into their parent at every level"),
+    ("input", "Read a line from stdin, printing an optional prompt first; null at end of input"),
+    ("to_string", "The text print would show for a value"),
+    ("repr", "Text of a value, eliding arrays and maps nested deeper than an optional depth"),
+    ("print_depth", "Limit how deeply nested arrays and maps print shows, or null for no limit"),
+    ("len", "Length of an array, or number of characters in a string"),
+    ("map", "Apply a function to each element of an array"),
+    ("filter", "Keep the array elements for which a function returns a truthy value"),
+    ("forEach", "Call a function on each array element"),
+    ("reduce", "Fold an array into one value, starting from an optional initial value"),
+    ("sort", "Sorted copy of an array, with an optional comparator"),
+    ("sort_by_key", "Copy of an array of objects sorted by one property"),
+    ("push", "Copy of an array with a value appended"),
+    ("pop", "Copy of an array without its last element"),
+    ("join", "Join array elements into a string with a separator"),
+    ("contains", "Whether an array contains a value"),
+    ("index_of", "Position of a value in an array, or -1"),
+    ("reverse", "Reversed copy of an array"),
+    ("slice", "Sub-array from start up to an optional end"),
+    ("find", "First array element matching a predicate, or null"),
+    ("find_index", "Position of the first element matching a predicate, or -1"),
+    ("any", "Whether a predicate holds for at least one array element"),
+    ("all", "Whether a predicate holds for every array element"),
+    ("zip_with", "Combine two arrays element by element with a function"),
+    ("chunk", "Split an array into sub-arrays of a given size"),
+    ("rotate", "Copy of an array rotated left by n positions"),
+    ("shuffle", "Copy of an array shuffled with a seed"),
+    ("sum", "Sum of an array of numbers, optionally skipping nulls"),
+    ("min", "Smallest number in an array, optionally skipping nulls"),
+    ("max", "Largest number in an array, optionally skipping nulls"),
+    ("avg", "Average of an array of numbers, optionally skipping nulls"),
+    ("num", "Convert a string, number, or boolean to a number"),
+    ("try_num", "Like num, but null instead of an error on bad input"),
+    ("round", "Round half away from zero, optionally to a number of decimals"),
+    ("round_half_up", "Round half towards positive infinity"),
+    ("round_half_even", "Round half to the nearest even integer"),
+    ("trunc", "Drop the fractional part of a number"),
+    ("format_number", "Group the digits of a number with a separator"),
+    ("parse_grouped", "Parse a number whose digits are grouped with a separator"),
+    ("wrap", "Wrap an integer index into 0 to length - 1"),
+    ("clamp", "Limit a number to a range"),
+    ("band", "Bitwise and of two integers"),
+    ("bor", "Bitwise or of two integers"),
+    ("bxor", "Bitwise xor of two integers"),
+    ("bnot", "Bitwise not of an integer"),
+    ("shl", "Shift an integer left by n bits"),
+    ("shr", "Arithmetic shift of an integer right by n bits"),
+    ("random", "Pseudo-random number in [0, 1)"),
+    ("random_int", "Pseudo-random integer from lo to hi inclusive"),
+    ("seed", "Restart the pseudo-random sequence from a seed"),
+    ("matches_glob", "Whether a string matches a pattern with * and ? wildcards"),
+    ("contains_all", "Whether a string contains every string in an array"),
+    ("with", "Copy of an object or map with some fields replaced"),
+    ("merge", "Shallow merge of two maps or two objects, the second one winning"),
+    ("keys", "Sorted keys of a map or object"),
+    ("own_keys", "Sorted properties an object's class doesn't inherit"),
+    ("values", "Values of a map or object, in key order"),
+    ("entries", "[key, value] pairs of a map or object, in key order"),
+    ("has", "Whether a map has a key or an object has a property"),
+    ("methods", "Sorted names of an object's methods"),
+    ("properties", "Sorted names of an object's properties"),
+    ("lazy", "Wrap a zero-argument function in a thunk"),
+    ("force", "Value of a thunk, computed on the first call only"),
+    ("assert", "Stop with an error unless a condition is truthy"),
+    ("loose_eq", "Equality that coerces numbers, numeric strings, and booleans"),
+    ("to_json", "Serialize a value as a JSON string"),
+    ("from_json", "Parse a JSON string"),
+    ("help", "Describe a builtin function, or list them all"),
+];
+
 pub fn call_builtin(name: &str, args: Vec<Value>) -> Result<Value, String> {
     match name {
         "typeof" => {
@@ -54,23 +684,1201 @@ pub fn call_builtin(name: &str, args: Vec<Value>) -> Result<Value, String> {
             }
             Ok(Value::String(args[0].type_name().to_string()))
         }
-        "print" => {
-            if args.len() != 1 {
-                return Err(format!("print expects 1 argument, got {}", args.len()));
-            }
-            println!("{}", args[0]);
-            Ok(Value::Null)
-        }
         "len" => {
             if args.len() != 1 {
                 return Err(format!("len expects 1 argument, got {}", args.len()));
             }
             match &args[0] {
                 Value::Array(arr) => Ok(Value::Number(arr.len() as f64)),
-                Value::String(s) => Ok(Value::Number(s.len() as f64)),
+                Value::String(s) => Ok(Value::Number(s.chars().count() as f64)),
                 _ => Err(format!("len expects Array or String, got {}", args[0].type_name())),
             }
         }
+        // Rounds half away from zero (2.5 -> 3, -2.5 -> -3), optionally to a number of decimals
+        "round" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(format!("round expects 1 or 2 arguments, got {}", args.len()));
+            }
+            let n = args[0].to_number()?;
+            if args.len() == 1 {
+                return Ok(Value::Number(n.round()));
+            }
+            let digits = args[1].to_number()?;
+            if digits.fract() != 0.0 || digits < 0.0 {
+                return Err(format!("round expects a non-negative integer number of digits, got {}", digits));
+            }
+            // When the scaled number overflows, `n` has no digits that far down to round
+            let factor = 10f64.powi(digits.min(i32::MAX as f64) as i32);
+            let scaled = n * factor;
+            if !scaled.is_finite() {
+                return Ok(Value::Number(n));
+            }
+            Ok(Value::Number(scaled.round() / factor))
+        }
+        // Rounds half towards positive infinity (2.5 -> 3, -2.5 -> -2)
+        "round_half_up" => {
+            if args.len() != 1 {
+                return Err(format!("round_half_up expects 1 argument, got {}", args.len()));
+            }
+            Ok(Value::Number((args[0].to_number()? + 0.5).floor()))
+        }
+        // Rounds half to the nearest even integer, a.k.a. banker's rounding (2.5 -> 2, 3.5 -> 4)
+        "round_half_even" => {
+            if args.len() != 1 {
+                return Err(format!("round_half_even expects 1 argument, got {}", args.len()));
+            }
+            Ok(Value::Number(args[0].to_number()?.round_ties_even()))
+        }
+        // Drops the fractional part, rounding towards zero (2.7 -> 2, -2.7 -> -2)
+        "trunc" => {
+            if args.len() != 1 {
+                return Err(format!("trunc expects 1 argument, got {}", args.len()));
+            }
+            Ok(Value::Number(args[0].to_number()?.trunc()))
+        }
+        // Arrays are values: push and pop return a new array rather than modifying their argument
+        "push" => {
+            if args.len() != 2 {
+                return Err(format!("push expects 2 arguments, got {}", args.len()));
+            }
+            match &args[0] {
+                Value::Array(arr) => {
+                    let mut result = arr.clone();
+                    result.push(args[1].clone());
+                    Ok(Value::Array(result))
+                }
+                _ => Err(format!("push expects an array, got {}", args[0].type_name())),
+            }
+        }
+        "pop" => {
+            if args.len() != 1 {
+                return Err(format!("pop expects 1 argument, got {}", args.len()));
+            }
+            match &args[0] {
+                Value::Array(arr) if arr.is_empty() => Err("pop expects a non-empty array".to_string()),
+                Value::Array(arr) => Ok(Value::Array(arr[..arr.len() - 1].to_vec())),
+                _ => Err(format!("pop expects an array, got {}", args[0].type_name())),
+            }
+        }
+        "join" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(format!("join expects 1 or 2 arguments, got {}", args.len()));
+            }
+            let separator = match args.get(1) {
+                Some(Value::String(s)) => s.as_str(),
+                Some(other) => return Err(format!("join expects a string separator, got {}", other.type_name())),
+                None => ",",
+            };
+            match &args[0] {
+                Value::Array(arr) => {
+                    let parts: Vec<String> = arr.iter().map(|v| v.to_string()).collect();
+                    Ok(Value::String(parts.join(separator)))
+                }
+                _ => Err(format!("join expects an array, got {}", args[0].type_name())),
+            }
+        }
+        "reverse" => {
+            if args.len() != 1 {
+                return Err(format!("reverse expects 1 argument, got {}", args.len()));
+            }
+            match &args[0] {
+                Value::Array(arr) => Ok(Value::Array(arr.iter().rev().cloned().collect())),
+                _ => Err(format!("reverse expects an array, got {}", args[0].type_name())),
+            }
+        }
+        // Negative indices count from the end; out of range indices are clamped
+        "slice" => {
+            if args.len() < 2 || args.len() > 3 {
+                return Err(format!("slice expects 2 or 3 arguments, got {}", args.len()));
+            }
+            match &args[0] {
+                Value::Array(arr) => {
+                    let len = arr.len() as i64;
+                    let clamp = |i: i64| if i < 0 { (len + i).max(0) } else { i.min(len) };
+                    let start = clamp(to_integer(&args[1], "slice")?);
+                    let end = match args.get(2) {
+                        Some(end) => clamp(to_integer(end, "slice")?),
+                        None => len,
+                    };
+                    if start >= end {
+                        return Ok(Value::Array(Vec::new()));
+                    }
+                    Ok(Value::Array(arr[start as usize..end as usize].to_vec()))
+                }
+                _ => Err(format!("slice expects an array, got {}", args[0].type_name())),
+            }
+        }
+        // The last chunk holds whatever is left over
+        "chunk" => {
+            if args.len() != 2 {
+                return Err(format!("chunk expects 2 arguments (array, size), got {}", args.len()));
+            }
+            let size = to_integer(&args[1], "chunk")?;
+            if size <= 0 {
+                return Err(format!("chunk expects a positive size, got {}", size));
+            }
+            match &args[0] {
+                Value::Array(arr) => Ok(Value::Array(
+                    arr.chunks(size as usize).map(|chunk| Value::Array(chunk.to_vec())).collect(),
+                )),
+                _ => Err(format!("chunk expects an array, got {}", args[0].type_name())),
+            }
+        }
+        // Bitwise operations work on integer-valued numbers
+        "band" | "bor" | "bxor" => {
+            if args.len() != 2 {
+                return Err(format!("{} expects 2 arguments, got {}", name, args.len()));
+            }
+            let a = to_integer(&args[0], name)?;
+            let b = to_integer(&args[1], name)?;
+            let result = match name {
+                "band" => a & b,
+                "bor" => a | b,
+                _ => a ^ b,
+            };
+            Ok(Value::Number(result as f64))
+        }
+        "bnot" => {
+            if args.len() != 1 {
+                return Err(format!("bnot expects 1 argument, got {}", args.len()));
+            }
+            Ok(Value::Number(!to_integer(&args[0], "bnot")? as f64))
+        }
+        // Shifts are arithmetic: shr keeps the sign of negative numbers
+        "shl" | "shr" => {
+            if args.len() != 2 {
+                return Err(format!("{} expects 2 arguments, got {}", name, args.len()));
+            }
+            let a = to_integer(&args[0], name)?;
+            let n = to_integer(&args[1], name)?;
+            if !(0..64).contains(&n) {
+                return Err(format!("{} expects a shift between 0 and 63, got {}", name, n));
+            }
+            let result = if name == "shl" { a << n } else { a >> n };
+            Ok(Value::Number(result as f64))
+        }
+        // Unlike `==`, numbers, numeric strings, and booleans compare by numeric value
+        "loose_eq" => {
+            if args.len() != 2 {
+                return Err(format!("loose_eq expects 2 arguments, got {}", args.len()));
+            }
+            let equal = match (&args[0], &args[1]) {
+                (a, b) if a.type_name() == b.type_name() => a == b,
+                (Value::Null, _) | (_, Value::Null) => false,
+                (a, b) => match (a.to_number(), b.to_number()) {
+                    (Ok(x), Ok(y)) => x == y,
+                    _ => false,
+                },
+            };
+            Ok(Value::Boolean(equal))
+        }
+        // The function runs on the first `force`, which is handled by the interpreter
+        "lazy" => {
+            if args.len() != 1 {
+                return Err(format!("lazy expects 1 argument, got {}", args.len()));
+            }
+            match &args[0] {
+                Value::Function { .. } | Value::Lambda { .. } | Value::NativeFunction { .. } => Ok(Value::Thunk {
+                    func: Box::new(args[0].clone()),
+                    memo: std::rc::Rc::new(std::cell::RefCell::new(None)),
+                }),
+                other => Err(format!("lazy expects a function, got {}", other.type_name())),
+            }
+        }
+        "assert" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(format!("assert expects 1 or 2 arguments, got {}", args.len()));
+            }
+            match args.get(1) {
+                _ if args[0].is_truthy() => Ok(Value::Null),
+                Some(message) => Err(format!("Assertion failed: {}", message)),
+                None => Err("Assertion failed".to_string()),
+            }
+        }
+        // format_number(1234567.5) is "1,234,567.5"; only the integer part is grouped
+        "format_number" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(format!("format_number expects 1 or 2 arguments, got {}", args.len()));
+            }
+            let Value::Number(n) = &args[0] else {
+                return Err(format!("format_number expects a number, got {}", args[0].type_name()));
+            };
+            let separator = match args.get(1) {
+                Some(Value::String(s)) => s.as_str(),
+                Some(other) => return Err(format!("format_number expects a String separator, got {}", other.type_name())),
+                None => ",",
+            };
+            if !n.is_finite() {
+                return Ok(Value::String(args[0].to_string()));
+            }
+            // Straight from the f64, which prints every integer digit; going through
+            // `Value`'s display would clamp whole numbers to the i64 range
+            let text = if *n == 0.0 { "0".to_string() } else { n.to_string() };
+            let (sign, unsigned) = text.strip_prefix('-').map_or(("", text.as_str()), |rest| ("-", rest));
+            let (integer, fraction) = unsigned.split_once('.').map_or((unsigned, None), |(i, f)| (i, Some(f)));
+            let mut result = sign.to_string();
+            for (i, digit) in integer.chars().enumerate() {
+                if i > 0 && (integer.len() - i) % 3 == 0 {
+                    result.push_str(separator);
+                }
+                result.push(digit);
+            }
+            if let Some(fraction) = fraction {
+                result.push('.');
+                result.push_str(fraction);
+            }
+            Ok(Value::String(result))
+        }
+        // The inverse of format_number: "1,234,567.5" -> 1234567.5
+        "parse_grouped" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(format!("parse_grouped expects 1 or 2 arguments, got {}", args.len()));
+            }
+            let Value::String(text) = &args[0] else {
+                return Err(format!("parse_grouped expects a String, got {}", args[0].type_name()));
+            };
+            let separator = match args.get(1) {
+                Some(Value::String(s)) if !s.is_empty() => s.as_str(),
+                Some(other) => {
+                    return Err(format!("parse_grouped expects a non-empty String separator, got {}", other))
+                }
+                None => ",",
+            };
+            let digits = text.replace(separator, "");
+            let unsigned = digits.strip_prefix('-').unwrap_or(&digits);
+            let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+            let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+            if integer.is_empty() || !is_digits(integer) || !is_digits(fraction) || digits.ends_with('.') {
+                return Err(format!("Cannot parse '{}' as a number grouped with '{}'", text, separator));
+            }
+            digits.parse::<f64>().map(Value::Number).map_err(|e| e.to_string())
+        }
+        // Always in 0..len, so wrap(-1, len(arr)) is the last index
+        "wrap" => {
+            if args.len() != 2 {
+                return Err(format!("wrap expects 2 arguments (index, length), got {}", args.len()));
+            }
+            let index = to_integer(&args[0], name)?;
+            let len = to_integer(&args[1], name)?;
+            if len <= 0 {
+                return Err(format!("wrap expects a positive length, got {}", len));
+            }
+            Ok(Value::Number(index.rem_euclid(len) as f64))
+        }
+        "clamp" => {
+            if args.len() != 3 {
+                return Err(format!("clamp expects 3 arguments (value, min, max), got {}", args.len()));
+            }
+            let value = args[0].to_number()?;
+            let min = args[1].to_number()?;
+            let max = args[2].to_number()?;
+            if min > max {
+                return Err(format!("clamp expects min <= max, got {} and {}", args[1], args[2]));
+            }
+            Ok(Value::Number(value.clamp(min, max)))
+        }
+        // Aggregates over an array of numbers. Nulls are an error unless the
+        // optional second argument is true, in which case they are skipped
+        "sum" | "min" | "max" | "avg" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(format!("{} expects 1 or 2 arguments, got {}", name, args.len()));
+            }
+            let skip_null = match args.get(1) {
+                Some(Value::Boolean(b)) => *b,
+                Some(other) => return Err(format!("{} expects a Boolean skip_null flag, got {}", name, other.type_name())),
+                None => false,
+            };
+            let items = match &args[0] {
+                Value::Array(items) => items,
+                other => return Err(format!("{} expects an Array, got {}", name, other.type_name())),
+            };
+            let mut numbers = Vec::with_capacity(items.len());
+            for item in items {
+                match item {
+                    Value::Number(n) => numbers.push(*n),
+                    Value::Null if skip_null => {}
+                    other => return Err(format!("{} expects an Array of Numbers, got {}", name, other.type_name())),
+                }
+            }
+            if numbers.is_empty() {
+                // The sum of nothing is 0; the other aggregates have no value
+                return Ok(if name == "sum" { Value::Number(0.0) } else { Value::Null });
+            }
+            let total: f64 = numbers.iter().sum();
+            Ok(Value::Number(match name {
+                "sum" => total,
+                "avg" => total / numbers.len() as f64,
+                "min" => numbers.iter().copied().fold(f64::INFINITY, f64::min),
+                _ => numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            }))
+        }
+        // Shallow: `b`'s entries replace `a`'s wholesale, nested values aren't merged
+        "merge" => {
+            if args.len() != 2 {
+                return Err(format!("merge expects 2 arguments, got {}", args.len()));
+            }
+            match (&args[0], &args[1]) {
+                (Value::Map(a), Value::Map(b)) => {
+                    let mut merged = a.clone();
+                    merged.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+                    Ok(Value::Map(merged))
+                }
+                (
+                    Value::Object { class_name, properties: a },
+                    Value::Object { class_name: other_class, properties: b },
+                ) if class_name == other_class => {
+                    let mut merged = a.clone();
+                    merged.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+                    Ok(Value::Object { class_name: class_name.clone(), properties: merged })
+                }
+                (Value::Object { class_name, .. }, Value::Object { class_name: other_class, .. }) => Err(format!(
+                    "merge expects objects of the same class, got {} and {}",
+                    class_name, other_class
+                )),
+                (a, b) => Err(format!(
+                    "merge expects two Maps or two Objects, got {} and {}",
+                    a.type_name(),
+                    b.type_name()
+                )),
+            }
+        }
+        // `*` matches any run of characters and `?` exactly one; the whole string must match
+        "matches_glob" => {
+            if args.len() != 2 {
+                return Err(format!("matches_glob expects 2 arguments (string, pattern), got {}", args.len()));
+            }
+            match (&args[0], &args[1]) {
+                (Value::String(text), Value::String(pattern)) => {
+                    let text: Vec<char> = text.chars().collect();
+                    let pattern: Vec<char> = pattern.chars().collect();
+                    Ok(Value::Boolean(glob_matches(&text, &pattern)))
+                }
+                (a, b) => Err(format!(
+                    "matches_glob expects two Strings, got {} and {}",
+                    a.type_name(),
+                    b.type_name()
+                )),
+            }
+        }
+        "contains_all" => {
+            if args.len() != 2 {
+                return Err(format!("contains_all expects 2 arguments (string, substrings), got {}", args.len()));
+            }
+            match (&args[0], &args[1]) {
+                (Value::String(text), Value::Array(parts)) => {
+                    let mut found = true;
+                    for part in parts {
+                        match part {
+                            Value::String(part) => found = found && text.contains(part.as_str()),
+                            other => {
+                                return Err(format!("contains_all expects an Array of Strings, got {}", other.type_name()))
+                            }
+                        }
+                    }
+                    Ok(Value::Boolean(found))
+                }
+                (a, b) => Err(format!(
+                    "contains_all expects a String and an Array, got {} and {}",
+                    a.type_name(),
+                    b.type_name()
+                )),
+            }
+        }
+        // Rotates left; a negative count rotates right, and counts wrap around the length
+        "rotate" => {
+            if args.len() != 2 {
+                return Err(format!("rotate expects 2 arguments (array, count), got {}", args.len()));
+            }
+            let n = to_integer(&args[1], name)?;
+            match &args[0] {
+                Value::Array(arr) => {
+                    let mut rotated = arr.clone();
+                    if !rotated.is_empty() {
+                        let len = rotated.len() as i64;
+                        rotated.rotate_left(n.rem_euclid(len) as usize);
+                    }
+                    Ok(Value::Array(rotated))
+                }
+                other => Err(format!("rotate expects an Array, got {}", other.type_name())),
+            }
+        }
+        "flatten" | "flatten_deep" => {
+            let depth = match (name, args.get(1)) {
+                ("flatten_deep", _) => None,
+                (_, None) => Some(1),
+                (_, Some(depth)) => match to_integer(depth, name)? {
+                    n if n >= 0 => Some(n as usize),
+                    n => return Err(format!("flatten expects a non-negative depth, got {}", n)),
+                },
+            };
+            match &args[0] {
+                Value::Array(arr) => {
+                    let mut flat = Vec::new();
+                    flatten_into(arr, depth, &mut flat);
+                    Ok(Value::Array(flat))
+                }
+                other => Err(format!("{} expects an Array, got {}", name, other.type_name())),
+            }
+        }
+        // The same seed always gives the same order
+        "shuffle" => {
+            if args.len() != 2 {
+                return Err(format!("shuffle expects 2 arguments (array, seed), got {}", args.len()));
+            }
+            let mut rng = XorShift::new(to_integer(&args[1], name)? as u64);
+            match &args[0] {
+                Value::Array(arr) => {
+                    // Fisher-Yates
+                    let mut shuffled = arr.clone();
+                    for i in (1..shuffled.len()).rev() {
+                        let j = rng.below(i as u64 + 1) as usize;
+                        shuffled.swap(i, j);
+                    }
+                    Ok(Value::Array(shuffled))
+                }
+                other => Err(format!("shuffle expects an Array, got {}", other.type_name())),
+            }
+        }
+        // `help("name")` describes one builtin; `help()` lists them all, one per line
+        "help" => {
+            let builtins = register_builtins();
+            let describe = |name: &str| -> Option<String> {
+                let (_, description) = DESCRIPTIONS.iter().find(|(n, _)| *n == name)?;
+                match builtins.get(name)? {
+                    Value::NativeFunction { arity, .. } => Some(format!("{}({}): {}", name, arity, description)),
+                    _ => None,
+                }
+            };
+            match args.first() {
+                None => {
+                    let mut names: Vec<&str> = DESCRIPTIONS.iter().map(|(name, _)| *name).collect();
+                    names.sort();
+                    Ok(Value::String(names.into_iter().filter_map(describe).collect::<Vec<_>>().join("\n")))
+                }
+                Some(Value::String(name)) => describe(name)
+                    .map(Value::String)
+                    .ok_or_else(|| format!("No builtin function named '{}'", name)),
+                Some(other) => Err(format!("help expects a String name, got {}", other.type_name())),
+            }
+        }
+        // The text `print` shows, with nesting beyond an optional depth elided as `...`
+        "repr" => {
+            if args.is_empty() || args.len() > 2 {
+                return Err(format!("repr expects 1 or 2 arguments, got {}", args.len()));
+            }
+            match args.get(1) {
+                None => Ok(Value::String(args[0].to_string())),
+                Some(depth) => {
+                    let depth = to_integer(depth, name)?;
+                    if depth < 0 {
+                        return Err(format!("repr expects a non-negative depth, got {}", depth));
+                    }
+                    Ok(Value::String(args[0].to_string_with_depth(depth as usize)))
+                }
+            }
+        }
+        // Surrounding whitespace is ignored: num(" 42 ") is 42
+        "num" | "try_num" => {
+            if args.len() != 1 {
+                return Err(format!("{} expects 1 argument, got {}", name, args.len()));
+            }
+            match (parse_num(&args[0]), name) {
+                (Ok(n), _) => Ok(Value::Number(n)),
+                (Err(_), "try_num") => Ok(Value::Null),
+                (Err(err), _) => Err(err),
+            }
+        }
+        "from_json" => {
+            if args.len() != 1 {
+                return Err(format!("from_json expects 1 argument, got {}", args.len()));
+            }
+            match &args[0] {
+                Value::String(text) => JsonParser::new(text).parse(),
+                other => Err(format!("from_json expects a String, got {}", other.type_name())),
+            }
+        }
         _ => Err(format!("Unknown builtin function: {}", name)),
     }
 }
+
+/// Convert an argument to an integer, rejecting fractional numbers
+fn to_integer(value: &Value, name: &str) -> Result<i64, String> {
+    let n = value.to_number()?;
+    if n.fract() != 0.0 {
+        return Err(format!("{} expects an integer, got {}", name, n));
+    }
+    Ok(n as i64)
+}
+
+/// A small xorshift64* pseudo-random generator: fast and deterministic for a
+/// given seed, but not suitable for anything security related
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed so that small seeds don't start with mostly zero bits;
+        // the state must never be zero
+        let state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ 0xD1B5_4A32_D192_ED03;
+        XorShift(if state == 0 { 1 } else { state })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `0..n`; `n` must be positive
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+/// Glob matching for `matches_glob`. On a mismatch, only the most recent `*` is
+/// retried (consuming one more character), which is enough since any earlier
+/// star could only match less of the text
+fn glob_matches(text: &[char], pattern: &[char]) -> bool {
+    let (mut t, mut p) = (0, 0);
+    let mut star: Option<(usize, usize)> = None; // pattern index after the last `*`, text index it resumes from
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if let Some((after_star, resume)) = star {
+            p = after_star;
+            t = resume + 1;
+            star = Some((after_star, resume + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Append `items` to `out`, splicing in nested arrays up to `depth` levels
+/// (all of them when `None`). Arrays are values, so nesting can't be cyclic
+fn flatten_into(items: &[Value], depth: Option<usize>, out: &mut Vec<Value>) {
+    for item in items {
+        match item {
+            Value::Array(inner) if depth != Some(0) => flatten_into(inner, depth.map(|d| d - 1), out),
+            other => out.push(other.clone()),
+        }
+    }
+}
+
+/// Parse a number from a string, number, or boolean
+fn parse_num(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::String(s) => s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite())
+            .ok_or_else(|| format!("Cannot convert '{}' to number", s)),
+        Value::Number(_) | Value::Boolean(_) => value.to_number(),
+        _ => Err(format!("Cannot convert {} to number", value.type_name())),
+    }
+}
+
+/// Recursive-descent parser for `from_json`. JSON objects become maps; errors
+/// report the character position they were found at
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonParser {
+    fn new(text: &str) -> Self {
+        JsonParser { chars: text.chars().collect(), pos: 0 }
+    }
+
+    fn parse(mut self) -> Result<Value, String> {
+        let value = self.value()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(value),
+            Some(c) => Err(self.error(&format!("unexpected '{}' after the value", c))),
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("Invalid JSON at position {}: {}", self.pos, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(&format!("expected '{}', found end of input", expected))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('t') => self.keyword("true", Value::Boolean(true)),
+            Some('f') => self.keyword("false", Value::Boolean(false)),
+            Some('n') => self.keyword("null", Value::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(&format!("invalid literal, expected '{}'", word)));
+            }
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut map = std::collections::HashMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Map(map));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value()?;
+            map.insert(key, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                _ => break,
+            }
+        }
+        self.expect('}')?;
+        Ok(Value::Map(map))
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                _ => break,
+            }
+        }
+        self.expect(']')?;
+        Ok(Value::Array(items))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut result = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            match c {
+                '"' => {
+                    self.pos += 1;
+                    return Ok(result);
+                }
+                '\\' => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            self.pos += 1;
+                            result.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    self.pos += 1;
+                    result.push(escaped);
+                }
+                c if c.is_control() => return Err(self.error("control character in string")),
+                c => {
+                    self.pos += 1;
+                    result.push(c);
+                }
+            }
+        }
+    }
+
+    /// The code point of a `\uXXXX` escape (just after the `u`), combining a
+    /// surrogate pair into one character
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid unicode escape"));
+        }
+        if self.peek() != Some('\\') || self.chars.get(self.pos + 1) != Some(&'u') {
+            return Err(self.error("unpaired surrogate in unicode escape"));
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("unpaired surrogate in unicode escape"));
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.peek().and_then(|c| c.to_digit(16));
+            let Some(digit) = digit else {
+                return Err(self.error("expected 4 hex digits in unicode escape"));
+            };
+            code = code * 16 + digit;
+            self.pos += 1;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some('0') => self.pos += 1,
+            Some(c) if c.is_ascii_digit() => self.digits(),
+            _ => return Err(self.error("expected a digit")),
+        }
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("expected a digit after '.'"));
+            }
+            self.digits();
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("expected a digit in the exponent"));
+            }
+            self.digits();
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| format!("Invalid JSON at position {}: invalid number", start))
+    }
+
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: Vec<Value>) -> Value {
+        call_builtin(name, args).unwrap()
+    }
+
+    #[test]
+    fn test_rounding_modes_on_ties() {
+        assert_eq!(call("round", vec![Value::Number(2.5)]), Value::Number(3.0));
+        assert_eq!(call("round", vec![Value::Number(-2.5)]), Value::Number(-3.0));
+        assert_eq!(call("round_half_up", vec![Value::Number(2.5)]), Value::Number(3.0));
+        assert_eq!(call("round_half_up", vec![Value::Number(-2.5)]), Value::Number(-2.0));
+        assert_eq!(call("round_half_even", vec![Value::Number(2.5)]), Value::Number(2.0));
+        assert_eq!(call("round_half_even", vec![Value::Number(3.5)]), Value::Number(4.0));
+        assert_eq!(call("trunc", vec![Value::Number(-2.7)]), Value::Number(-2.0));
+    }
+
+    #[test]
+    fn test_round_to_decimal_places() {
+        assert_eq!(call("round", vec![Value::Number(1.23456), Value::Number(2.0)]), Value::Number(1.23));
+        assert_eq!(call("round", vec![Value::Number(1.5), Value::Number(0.0)]), Value::Number(2.0));
+        assert_eq!(call("round", vec![Value::Number(2.5), Value::Number(400.0)]), Value::Number(2.5));
+        assert_eq!(call("round", vec![Value::Number(1e300), Value::Number(20.0)]), Value::Number(1e300));
+        assert_eq!(
+            call_builtin("round", vec![Value::Number(1.0), Value::Number(0.5)]).unwrap_err(),
+            "round expects a non-negative integer number of digits, got 0.5"
+        );
+        assert_eq!(
+            call_builtin("round", vec![Value::Number(1234.0), Value::Number(-2.0)]).unwrap_err(),
+            "round expects a non-negative integer number of digits, got -2"
+        );
+    }
+
+    #[test]
+    fn test_slice_negative_and_clamped_indices() {
+        let arr = Value::Array((0..5).map(|n| Value::Number(n as f64)).collect());
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        assert_eq!(call("slice", vec![arr.clone(), Value::Number(-2.0)]), numbers(&[3.0, 4.0]));
+        assert_eq!(call("slice", vec![arr.clone(), Value::Number(1.0), Value::Number(99.0)]), numbers(&[1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(call("slice", vec![arr.clone(), Value::Number(3.0), Value::Number(1.0)]), numbers(&[]));
+        assert!(call_builtin("pop", vec![Value::Array(Vec::new())]).is_err());
+    }
+
+    #[test]
+    fn test_len_counts_characters() {
+        assert_eq!(call("len", vec![Value::String("héllo".to_string())]), Value::Number(5.0));
+        assert_eq!(call("len", vec![Value::String("😀".to_string())]), Value::Number(1.0));
+        assert_eq!(call("len", vec![Value::String(String::new())]), Value::Number(0.0));
+    }
+
+    #[test]
+    fn test_bitwise_helpers() {
+        let n = Value::Number;
+        assert_eq!(call("band", vec![n(12.0), n(10.0)]), n(8.0));
+        assert_eq!(call("bor", vec![n(12.0), n(10.0)]), n(14.0));
+        assert_eq!(call("bxor", vec![n(12.0), n(10.0)]), n(6.0));
+        assert_eq!(call("bnot", vec![n(0.0)]), n(-1.0));
+        assert_eq!(call("shl", vec![n(1.0), n(4.0)]), n(16.0));
+        assert_eq!(call("shr", vec![n(-16.0), n(2.0)]), n(-4.0));
+
+        for name in ["band", "bor", "bxor", "shl", "shr"] {
+            let err = call_builtin(name, vec![n(1.5), n(1.0)]).unwrap_err();
+            assert_eq!(err, format!("{} expects an integer, got 1.5", name));
+        }
+        assert!(call_builtin("bnot", vec![n(0.5)]).is_err());
+        assert!(call_builtin("shl", vec![n(1.0), n(64.0)]).is_err());
+        assert!(call_builtin("shr", vec![n(1.0), n(-1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_loose_eq_coerces() {
+        let loose_eq = |a: Value, b: Value| call("loose_eq", vec![a, b]);
+        let s = |text: &str| Value::String(text.to_string());
+        assert_eq!(loose_eq(Value::Number(5.0), s("5")), Value::Boolean(true));
+        assert_eq!(loose_eq(s("5.0"), Value::Number(5.0)), Value::Boolean(true));
+        assert_eq!(loose_eq(Value::Boolean(true), Value::Number(1.0)), Value::Boolean(true));
+        assert_eq!(loose_eq(s("5"), s("5.0")), Value::Boolean(false));
+        assert_eq!(loose_eq(Value::Number(5.0), s("five")), Value::Boolean(false));
+        assert_eq!(loose_eq(Value::Null, Value::Number(0.0)), Value::Boolean(false));
+        assert_eq!(loose_eq(Value::Null, Value::Null), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_num_and_try_num() {
+        let s = |text: &str| Value::String(text.to_string());
+        assert_eq!(call("num", vec![s("42.5")]), Value::Number(42.5));
+        assert_eq!(call("try_num", vec![s("42.5")]), Value::Number(42.5));
+        assert_eq!(call("try_num", vec![s("  7\n")]), Value::Number(7.0));
+        assert_eq!(call("try_num", vec![s("abc")]), Value::Null);
+        assert_eq!(call("try_num", vec![s("nan")]), Value::Null);
+        assert_eq!(call("try_num", vec![Value::Array(Vec::new())]), Value::Null);
+        assert_eq!(call_builtin("num", vec![s("abc")]).unwrap_err(), "Cannot convert 'abc' to number");
+    }
+
+    #[test]
+    fn test_wrap_index() {
+        let wrap = |i: f64, len: f64| call("wrap", vec![Value::Number(i), Value::Number(len)]);
+        assert_eq!(wrap(2.0, 5.0), Value::Number(2.0));
+        assert_eq!(wrap(-1.0, 5.0), Value::Number(4.0));
+        assert_eq!(wrap(-6.0, 5.0), Value::Number(4.0));
+        assert_eq!(wrap(5.0, 5.0), Value::Number(0.0));
+        assert_eq!(wrap(12.0, 5.0), Value::Number(2.0));
+        assert_eq!(
+            call_builtin("wrap", vec![Value::Number(1.0), Value::Number(0.0)]).unwrap_err(),
+            "wrap expects a positive length, got 0"
+        );
+    }
+
+    #[test]
+    fn test_aggregates_strict_and_skipping_nulls() {
+        let data = || Value::Array(vec![Value::Number(4.0), Value::Null, Value::Number(1.0), Value::Number(7.0)]);
+        let skipping = |name: &str| call(name, vec![data(), Value::Boolean(true)]);
+        assert_eq!(skipping("sum"), Value::Number(12.0));
+        assert_eq!(skipping("min"), Value::Number(1.0));
+        assert_eq!(skipping("max"), Value::Number(7.0));
+        assert_eq!(skipping("avg"), Value::Number(4.0));
+        for name in ["sum", "min", "max", "avg"] {
+            assert_eq!(
+                call_builtin(name, vec![data()]).unwrap_err(),
+                format!("{} expects an Array of Numbers, got Null", name)
+            );
+            assert_eq!(
+                call_builtin(name, vec![data(), Value::Boolean(false)]).unwrap_err(),
+                format!("{} expects an Array of Numbers, got Null", name)
+            );
+        }
+        let only_nulls = || Value::Array(vec![Value::Null]);
+        assert_eq!(call("sum", vec![only_nulls(), Value::Boolean(true)]), Value::Number(0.0));
+        assert_eq!(call("avg", vec![only_nulls(), Value::Boolean(true)]), Value::Null);
+    }
+
+    #[test]
+    fn test_merge_prefers_second_operand() {
+        let map = |entries: &[(&str, f64)]| {
+            Value::Map(entries.iter().map(|(k, v)| (k.to_string(), Value::Number(*v))).collect())
+        };
+        assert_eq!(
+            call("merge", vec![map(&[("a", 1.0), ("b", 2.0)]), map(&[("b", 3.0), ("c", 4.0)])]),
+            map(&[("a", 1.0), ("b", 3.0), ("c", 4.0)])
+        );
+        assert_eq!(
+            call_builtin("merge", vec![map(&[]), Value::Array(vec![])]).unwrap_err(),
+            "merge expects two Maps or two Objects, got Map and Array"
+        );
+    }
+
+    #[test]
+    fn test_matches_glob() {
+        let glob = |text: &str, pattern: &str| {
+            call("matches_glob", vec![Value::String(text.to_string()), Value::String(pattern.to_string())])
+        };
+        assert_eq!(glob("report.txt", "*.txt"), Value::Boolean(true));
+        assert_eq!(glob("report.txt", "report.???"), Value::Boolean(true));
+        assert_eq!(glob("a-b-c", "a*c"), Value::Boolean(true));
+        assert_eq!(glob("abcbc", "*bc"), Value::Boolean(true));
+        assert_eq!(glob("", "*"), Value::Boolean(true));
+        assert_eq!(glob("report.txt", "*.csv"), Value::Boolean(false));
+        assert_eq!(glob("report.txt", "report.??"), Value::Boolean(false));
+        assert_eq!(glob("abc", "ab"), Value::Boolean(false));
+        assert_eq!(glob("", "?"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_contains_all() {
+        let contains_all = |parts: &[&str]| {
+            call(
+                "contains_all",
+                vec![
+                    Value::String("the quick brown fox".to_string()),
+                    Value::Array(parts.iter().map(|p| Value::String(p.to_string())).collect()),
+                ],
+            )
+        };
+        assert_eq!(contains_all(&["quick", "fox"]), Value::Boolean(true));
+        assert_eq!(contains_all(&[]), Value::Boolean(true));
+        assert_eq!(contains_all(&["quick", "dog"]), Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_rotate() {
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        let rotate = |n: f64| call("rotate", vec![numbers(&[1.0, 2.0, 3.0, 4.0]), Value::Number(n)]);
+        assert_eq!(rotate(1.0), numbers(&[2.0, 3.0, 4.0, 1.0]));
+        assert_eq!(rotate(-1.0), numbers(&[4.0, 1.0, 2.0, 3.0]));
+        assert_eq!(rotate(6.0), numbers(&[3.0, 4.0, 1.0, 2.0]));
+        assert_eq!(rotate(0.0), numbers(&[1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(call("rotate", vec![numbers(&[]), Value::Number(3.0)]), numbers(&[]));
+    }
+
+    #[test]
+    fn test_shuffle_is_reproducible() {
+        let items = || Value::Array((0..10).map(|n| Value::Number(n as f64)).collect());
+        let shuffle = |seed: f64| call("shuffle", vec![items(), Value::Number(seed)]);
+        assert_eq!(shuffle(42.0), shuffle(42.0));
+        assert_ne!(shuffle(42.0), shuffle(7.0));
+        assert_ne!(shuffle(42.0), items());
+        // Shuffling only reorders the elements
+        let Value::Array(mut sorted) = shuffle(42.0) else { panic!("shuffle should return an array") };
+        sorted.sort_by(|a, b| a.to_number().unwrap().total_cmp(&b.to_number().unwrap()));
+        assert_eq!(Value::Array(sorted), items());
+    }
+
+    #[test]
+    fn test_help_describes_builtins() {
+        assert_eq!(
+            call("help", vec![Value::String("len".to_string())]),
+            Value::String("len(1): Length of an array, or number of characters in a string".to_string())
+        );
+        assert_eq!(
+            call_builtin("help", vec![Value::String("nope".to_string())]).unwrap_err(),
+            "No builtin function named 'nope'"
+        );
+        // Every builtin has a description, so `help()` lists one line per builtin
+        let Value::String(all) = call("help", vec![]) else { panic!("help() should return a string") };
+        assert_eq!(all.lines().count(), register_builtins().len());
+    }
+
+    #[test]
+    fn test_repr_elides_beyond_depth() {
+        // [1, [2, [3]], {a: [4]}]
+        let nested = Value::Array(vec![
+            Value::Number(1.0),
+            Value::Array(vec![Value::Number(2.0), Value::Array(vec![Value::Number(3.0)])]),
+            Value::Map([("a".to_string(), Value::Array(vec![Value::Number(4.0)]))].into_iter().collect()),
+        ]);
+        let repr = |depth: Option<f64>| {
+            let mut args = vec![nested.clone()];
+            args.extend(depth.map(Value::Number));
+            call("repr", args)
+        };
+        assert_eq!(repr(None), Value::String("[1, [2, [3]], {a: [4]}]".to_string()));
+        assert_eq!(repr(Some(3.0)), Value::String("[1, [2, [3]], {a: [4]}]".to_string()));
+        assert_eq!(repr(Some(2.0)), Value::String("[1, [2, [...]], {a: [...]}]".to_string()));
+        assert_eq!(repr(Some(1.0)), Value::String("[1, [...], {...}]".to_string()));
+        assert_eq!(repr(Some(0.0)), Value::String("[...]".to_string()));
+    }
+
+    #[test]
+    fn test_clamp() {
+        let clamp = |x: f64| call("clamp", vec![Value::Number(x), Value::Number(0.0), Value::Number(10.0)]);
+        assert_eq!(clamp(-3.0), Value::Number(0.0));
+        assert_eq!(clamp(4.5), Value::Number(4.5));
+        assert_eq!(clamp(11.0), Value::Number(10.0));
+        assert!(call_builtin("clamp", vec![Value::Number(1.0), Value::Number(2.0), Value::Number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let parsed = call("from_json", vec![Value::String(r#"{"a":[1,2],"b":true}"#.to_string())]);
+        let mut expected = std::collections::HashMap::new();
+        expected.insert("a".to_string(), Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]));
+        expected.insert("b".to_string(), Value::Boolean(true));
+        assert_eq!(parsed, Value::Map(expected));
+
+        let text = r#" [ -1.5e2, "tab\tand \u00e9 \ud83d\ude00", null, {} ] "#;
+        assert_eq!(
+            call("from_json", vec![Value::String(text.to_string())]),
+            Value::Array(vec![
+                Value::Number(-150.0),
+                Value::String("tab\tand é 😀".to_string()),
+                Value::Null,
+                Value::Map(std::collections::HashMap::new()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        let parse = |text: &str| call_builtin("from_json", vec![Value::String(text.to_string())]).unwrap_err();
+        assert_eq!(parse(r#"{"a": [1, 2}"#), "Invalid JSON at position 11: expected ']', found '}'");
+        assert_eq!(parse("[1, 2] 3"), "Invalid JSON at position 7: unexpected '3' after the value");
+        assert_eq!(parse(r#"{"a": tru}"#), "Invalid JSON at position 9: invalid literal, expected 'true'");
+        assert_eq!(parse(""), "Invalid JSON at position 0: unexpected end of input");
+    }
+
+    #[test]
+    fn test_flatten() {
+        let n = Value::Number;
+        let arr = Value::Array;
+        // [1, [2, [3, [4]]]]
+        let nested = || arr(vec![n(1.0), arr(vec![n(2.0), arr(vec![n(3.0), arr(vec![n(4.0)])])])]);
+        assert_eq!(
+            call("flatten", vec![nested()]),
+            arr(vec![n(1.0), n(2.0), arr(vec![n(3.0), arr(vec![n(4.0)])])])
+        );
+        assert_eq!(
+            call("flatten", vec![nested(), n(2.0)]),
+            arr(vec![n(1.0), n(2.0), n(3.0), arr(vec![n(4.0)])])
+        );
+        assert_eq!(call("flatten", vec![nested(), n(0.0)]), nested());
+        assert_eq!(call("flatten_deep", vec![nested()]), arr(vec![n(1.0), n(2.0), n(3.0), n(4.0)]));
+        assert_eq!(
+            call_builtin("flatten", vec![nested(), n(-1.0)]).unwrap_err(),
+            "flatten expects a non-negative depth, got -1"
+        );
+    }
+
+    #[test]
+    fn test_chunk() {
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        let chunk = |size: f64| call("chunk", vec![numbers(&[1.0, 2.0, 3.0, 4.0]), Value::Number(size)]);
+        assert_eq!(chunk(2.0), Value::Array(vec![numbers(&[1.0, 2.0]), numbers(&[3.0, 4.0])]));
+        assert_eq!(chunk(3.0), Value::Array(vec![numbers(&[1.0, 2.0, 3.0]), numbers(&[4.0])]));
+        assert_eq!(chunk(10.0), Value::Array(vec![numbers(&[1.0, 2.0, 3.0, 4.0])]));
+        assert_eq!(
+            call_builtin("chunk", vec![numbers(&[1.0]), Value::Number(0.0)]).unwrap_err(),
+            "chunk expects a positive size, got 0"
+        );
+    }
+
+    #[test]
+    fn test_parse_grouped() {
+        let parse = |text: &str| call_builtin("parse_grouped", vec![Value::String(text.to_string())]);
+        assert_eq!(parse("1,234,567"), Ok(Value::Number(1234567.0)));
+        assert_eq!(parse("-1,234.5"), Ok(Value::Number(-1234.5)));
+        assert_eq!(parse("999"), Ok(Value::Number(999.0)));
+        assert_eq!(
+            call("parse_grouped", vec![Value::String("1 000 000.5".to_string()), Value::String(" ".to_string())]),
+            Value::Number(1000000.5)
+        );
+        // Round trip
+        let formatted = call("format_number", vec![Value::Number(-9876543.25)]);
+        assert_eq!(call("parse_grouped", vec![formatted]), Value::Number(-9876543.25));
+
+        for bad in ["12a,345", "1.234.5", "", "-", "1,234.", "1 234"] {
+            assert_eq!(
+                parse(bad),
+                Err(format!("Cannot parse '{}' as a number grouped with ','", bad)),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_number() {
+        let format = |n: f64| call("format_number", vec![Value::Number(n)]);
+        assert_eq!(format(1234567.0), Value::String("1,234,567".to_string()));
+        assert_eq!(format(999.0), Value::String("999".to_string()));
+        assert_eq!(format(-1234.0), Value::String("-1,234".to_string()));
+        assert_eq!(format(-123.0), Value::String("-123".to_string()));
+        assert_eq!(format(1234567.891), Value::String("1,234,567.891".to_string()));
+        assert_eq!(
+            call("format_number", vec![Value::Number(1000000.5), Value::String(" ".to_string())]),
+            Value::String("1 000 000.5".to_string())
+        );
+        // Beyond the i64 range
+        assert_eq!(format(1e21), Value::String("1,000,000,000,000,000,000,000".to_string()));
+        assert_eq!(format(-1e19), Value::String("-10,000,000,000,000,000,000".to_string()));
+        assert_eq!(format(-0.0), Value::String("0".to_string()));
+    }
+
+    #[test]
+    fn test_assert() {
+        assert_eq!(call("assert", vec![Value::Boolean(true)]), Value::Null);
+        assert_eq!(call("assert", vec![Value::Number(1.0), Value::String("unused".to_string())]), Value::Null);
+        assert_eq!(call_builtin("assert", vec![Value::Boolean(false)]).unwrap_err(), "Assertion failed");
+        assert_eq!(
+            call_builtin("assert", vec![Value::Null, Value::String("total must be set".to_string())]).unwrap_err(),
+            "Assertion failed: total must be set"
+        );
+    }
+}
@@ -2,27 +2,214 @@ pub mod value;
 pub mod builtins;
 
 use crate::parser::ast::*;
-use value::Value;
-use std::collections::HashMap;
+use crate::resolver::{Resolution, Resolver};
+use value::{Arity, Value};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// Builtins that can also be called as methods on an array receiver
+const ARRAY_METHODS: &[&str] = &[
+    "map", "filter", "reduce", "sort", "push", "pop", "join", "contains", "index_of", "reverse", "slice",
+    "find", "find_index", "forEach", "any", "all", "chunk", "zip_with", "sum", "min", "max", "avg",
+    "sort_by_key", "flatten", "flatten_deep", "take_while", "drop_while",
+];
+
+/// An error raised while running a program, with the line of the innermost
+/// statement that was executing when it happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub line: Option<usize>,
+}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        RuntimeError { message, line: None }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "Error at line {}: {}", line, self.message),
+            None => write!(f, "Error: {}", self.message),
+        }
+    }
+}
+
+/// Callback run before each statement executes
+pub type DebugHook = Box<dyn FnMut(&Stmt)>;
+
+/// A builtin supplied by the embedding application
+pub type NativeFn = Box<dyn Fn(Vec<Value>) -> Result<Value, String>>;
+
+/// Operation counts collected while profiling. Cells so that lookups, which
+/// only borrow the interpreter, can be counted too
+#[derive(Default)]
+struct Counters {
+    expressions: Cell<u64>,
+    calls: Cell<u64>,
+    scope_pushes: Cell<u64>,
+    lookups: Cell<u64>,
+}
 
 pub struct Interpreter {
     globals: HashMap<String, Value>,
     scopes: Vec<HashMap<String, Value>>,
     in_context: bool, // Track if we're executing within a function or method
+    trace: bool,      // Log each statement to stderr before executing it
+    strict: bool,     // Refuse implicit string/boolean to number coercions in operators
+    undefined_is_null: bool, // Reading an undefined variable gives null instead of an error
+    method_frames: Vec<(Value, String)>, // (defining class, name) of the methods being executed, used by `super` and member checks
+    debug_hook: Option<DebugHook>,
+    profile: bool, // Count operations for `profile_report`
+    counters: Counters,
+    error_line: Option<usize>, // Line of the innermost statement that failed, for `RuntimeError`
+    rng: builtins::XorShift,   // Source for `random` and `random_int`, reseeded by `seed`
+    print_depth: Option<usize>, // Nesting shown by `print` before eliding with `...`, set by `print_depth`
+    natives: HashMap<String, NativeFn>, // Registered with `register_native`, checked before the builtins
+    array_method_arities: HashMap<String, Arity>, // Of the builtins behind ARRAY_METHODS, which skip the global lookup
+    input: Option<Box<dyn BufRead>>, // Read by `input`; stdin when unset
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         let globals = builtins::register_builtins();
+        let array_method_arities = ARRAY_METHODS
+            .iter()
+            .filter_map(|name| match globals.get(*name) {
+                Some(Value::NativeFunction { arity, .. }) => Some((name.to_string(), *arity)),
+                _ => None,
+            })
+            .collect();
         Interpreter {
             globals,
             scopes: Vec::new(),
             in_context: false,
+            trace: false,
+            strict: false,
+            undefined_is_null: false,
+            method_frames: Vec::new(),
+            debug_hook: None,
+            profile: false,
+            counters: Counters::default(),
+            error_line: None,
+            print_depth: None,
+            natives: HashMap::new(),
+            array_method_arities,
+            input: None,
+            rng: builtins::XorShift::new(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos() as u64),
+            ),
+        }
+    }
+
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+
+    /// In strict mode arithmetic and comparison operators only accept numbers
+    /// (or two strings/booleans for comparisons), so `"5" - 3` is an error
+    /// instead of `2`
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    /// When enabled, reading a variable that was never defined gives `null`
+    /// instead of an error, which suits template-style scripts such as
+    /// `title ?? "Untitled"`. Calling an undefined function is still an error
+    pub fn set_undefined_is_null(&mut self, enabled: bool) {
+        self.undefined_is_null = enabled;
+    }
+
+    /// Register a callback invoked before each statement executes, e.g. to
+    /// implement breakpoints or stepping
+    pub fn set_debug_hook(&mut self, hook: DebugHook) {
+        self.debug_hook = Some(hook);
+    }
+
+    /// Make a Rust function callable from scripts as a global named `name`,
+    /// taking exactly `arity` arguments. A name that is already a builtin is
+    /// replaced
+    pub fn register_native(&mut self, name: &str, arity: usize, f: NativeFn) {
+        self.natives.insert(name.to_string(), f);
+        self.globals.insert(
+            name.to_string(),
+            Value::NativeFunction {
+                name: name.to_string(),
+                arity: Arity::Exact(arity),
+            },
+        );
+    }
+
+    /// Read lines for `input` from `reader` instead of stdin
+    pub fn set_input(&mut self, reader: Box<dyn BufRead>) {
+        self.input = Some(reader);
+    }
+
+    pub fn set_profile(&mut self, enabled: bool) {
+        self.profile = enabled;
+    }
+
+    /// Operation counts gathered since profiling was enabled
+    pub fn profile_report(&self) -> String {
+        let counters = &self.counters;
+        format!(
+            "[profile] expressions evaluated: {}\n[profile] function calls: {}\n[profile] scope pushes: {}\n[profile] variable lookups: {}",
+            counters.expressions.get(),
+            counters.calls.get(),
+            counters.scope_pushes.get(),
+            counters.lookups.get()
+        )
+    }
+
+    /// Global variables sorted by name, leaving out the builtin functions
+    pub fn global_bindings(&self) -> Vec<(&String, &Value)> {
+        let mut bindings: Vec<_> = self
+            .globals
+            .iter()
+            .filter(|(_, value)| !matches!(value, Value::NativeFunction { .. }))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(b.0));
+        bindings
+    }
+
+    /// Check `program` statically against the globals defined so far, returning
+    /// where each variable read resolves or every undefined variable
+    pub fn resolve(&self, program: &Program) -> Result<Vec<Resolution>, Vec<String>> {
+        Resolver::new(self.globals.keys().cloned()).resolve(program)
+    }
+
+    /// Define or replace a global variable, e.g. the REPL's `_`
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.to_string(), value);
+    }
+
+    fn count(&self, counter: impl Fn(&Counters) -> &Cell<u64>) {
+        if self.profile {
+            let counter = counter(&self.counters);
+            counter.set(counter.get() + 1);
         }
     }
 
     fn push_scope(&mut self) {
-        self.scopes.push(HashMap::new());
+        self.push_scope_with(HashMap::new());
+    }
+
+    fn push_scope_with(&mut self, scope: HashMap<String, Value>) {
+        self.count(|c| &c.scope_pushes);
+        self.scopes.push(scope);
     }
 
     fn pop_scope(&mut self) {
@@ -30,9 +217,13 @@ impl Interpreter {
     }
 
     fn get_variable(&self, name: &str) -> Result<Value, String> {
+        self.count(|c| &c.lookups);
         // Search in scopes from innermost to outermost
         for scope in self.scopes.iter().rev() {
             if let Some(value) = scope.get(name) {
+                if name == "this" {
+                    return Ok(Self::receiver(value, scope));
+                }
                 return Ok(value.clone());
             }
         }
@@ -49,6 +240,21 @@ impl Interpreter {
         // Try to update in scopes first
         for scope in self.scopes.iter_mut().rev() {
             if scope.contains_key(&name) {
+                // A method's receiver scope also holds the object's properties as
+                // variables; keep them in step with `this`, except for properties
+                // private to another class, which the receiver scope leaves out
+                if let ("this", Value::Object { properties, .. }) = (name.as_str(), &value) {
+                    let hidden: HashSet<&String> = match scope.get("this") {
+                        Some(Value::Object { properties: old, .. }) => {
+                            old.keys().filter(|prop_name| !scope.contains_key(*prop_name)).collect()
+                        }
+                        _ => HashSet::new(),
+                    };
+                    let visible: Vec<_> = properties.iter().filter(|(prop_name, _)| !hidden.contains(prop_name)).collect();
+                    for (prop_name, prop_val) in visible {
+                        scope.insert(prop_name.clone(), prop_val.clone());
+                    }
+                }
                 scope.insert(name, value);
                 return;
             }
@@ -58,6 +264,21 @@ impl Interpreter {
         self.globals.insert(name, value);
     }
 
+    /// The current `this`, with properties read from the receiver scope so that
+    /// assignments made through plain property names are visible
+    fn receiver(this: &Value, scope: &HashMap<String, Value>) -> Value {
+        match this {
+            Value::Object { class_name, properties } => Value::Object {
+                class_name: class_name.clone(),
+                properties: properties
+                    .iter()
+                    .map(|(name, val)| (name.clone(), scope.get(name).unwrap_or(val).clone()))
+                    .collect(),
+            },
+            _ => this.clone(),
+        }
+    }
+
     fn define_variable(&mut self, name: String, value: Value) {
         if let Some(scope) = self.scopes.last_mut() {
             scope.insert(name, value);
@@ -66,36 +287,152 @@ impl Interpreter {
         }
     }
 
-    pub fn execute(&mut self, program: &Program) -> Result<(), String> {
+    /// Plain `name = value`: updates an existing variable, or defines a new one in
+    /// the innermost scope
+    fn assign_variable(&mut self, name: &str, value: Value) {
+        if self.get_variable(name).is_ok() {
+            self.set_variable(name.to_string(), value);
+        } else {
+            self.define_variable(name.to_string(), value);
+        }
+    }
+
+    /// Lex, parse, and run source code in this interpreter's global scope, so
+    /// that later calls see the variables and functions it defines
+    pub fn run(&mut self, source: &str) -> Result<(), RuntimeError> {
+        let program = Self::parse_source(source)?;
+        self.execute(&program)
+    }
+
+    /// Like `run`, but returns the value of the final statement when it is an
+    /// expression, and null otherwise
+    ///
+    /// ```
+    /// use platypus::{Interpreter, Value};
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// assert_eq!(interpreter.eval("x = 20\nx + 1").unwrap(), Value::Number(21.0));
+    /// assert_eq!(interpreter.eval("x * 2").unwrap(), Value::Number(40.0));
+    /// ```
+    pub fn eval(&mut self, source: &str) -> Result<Value, RuntimeError> {
+        let mut program = Self::parse_source(source)?;
+        let last = match program.statements.last() {
+            Some(Stmt { kind: StmtKind::Expr(_), .. }) => program.statements.pop(),
+            _ => None,
+        };
+        self.execute(&program)?;
+        match last {
+            Some(Stmt { kind: StmtKind::Expr(expr), line }) => {
+                let depth = self.call_depth();
+                self.evaluate_expr(&expr).map_err(|message| {
+                    self.unwind(depth);
+                    RuntimeError { message, line: Some(line) }
+                })
+            }
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// How deep into scopes and method calls execution is, for `unwind`
+    fn call_depth(&self) -> (usize, usize, bool) {
+        (self.scopes.len(), self.method_frames.len(), self.in_context)
+    }
+
+    /// Drop the scopes and method frames an error left behind when it cut calls
+    /// and blocks short, so the next `run` or `eval` starts where this one did
+    fn unwind(&mut self, (scopes, frames, in_context): (usize, usize, bool)) {
+        self.scopes.truncate(scopes);
+        self.method_frames.truncate(frames);
+        self.in_context = in_context;
+    }
+
+    fn parse_source(source: &str) -> Result<Program, String> {
+        let tokens = crate::lexer::Lexer::new(source.to_string()).tokenize()?;
+        crate::parser::Parser::new(tokens).parse()
+    }
+
+    pub fn execute(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        self.error_line = None;
+        // Top-level functions are hoisted, so they can be called before their
+        // declaration and can call each other regardless of order
+        for stmt in &program.statements {
+            if let StmtKind::FuncDecl { name, params, body, .. } = &stmt.kind {
+                let func = self.function_value(params, body);
+                self.define_variable(name.clone(), func);
+            }
+        }
+        let depth = self.call_depth();
         for stmt in &program.statements {
-            self.execute_stmt(stmt)?;
+            if let Err(message) = self.execute_stmt(stmt) {
+                self.unwind(depth);
+                return Err(RuntimeError { message, line: self.error_line.take() });
+            }
         }
         Ok(())
     }
 
     fn execute_stmt(&mut self, stmt: &Stmt) -> Result<Option<Value>, String> {
-        match stmt {
-            Stmt::VarDecl { name, value } => {
+        if self.trace {
+            eprintln!("[trace] line {}: {}", stmt.line, stmt.kind.name());
+        }
+        if let Some(hook) = self.debug_hook.as_mut() {
+            hook(stmt);
+        }
+
+        let result = self.run_stmt(stmt);
+        if result.is_err() && self.error_line.is_none() {
+            self.error_line = Some(stmt.line);
+        }
+        result
+    }
+
+    fn run_stmt(&mut self, stmt: &Stmt) -> Result<Option<Value>, String> {
+        match &stmt.kind {
+            StmtKind::VarDecl { name, value } => {
                 let val = self.evaluate_expr(value)?;
-                // Check if variable already exists; if so, update it; otherwise, create new one
-                if self.get_variable(name).is_ok() {
-                    self.set_variable(name.clone(), val);
-                } else {
-                    self.define_variable(name.clone(), val);
-                }
+                self.assign_variable(name, val);
                 Ok(None)
             }
-            Stmt::FuncDecl { name, params, body, .. } => {
-                let closure = self.capture_closure();
-                let func = Value::Function {
-                    params: params.clone(),
-                    body: body.clone(),
-                    closure,
+            StmtKind::Destructure { names, rest, value } => {
+                let elements = match self.evaluate_expr(value)? {
+                    Value::Array(elements) => elements,
+                    other => return Err(format!("Cannot destructure {}, expected an Array", other.type_name())),
+                };
+                let fits = match rest {
+                    Some(_) => elements.len() >= names.len(),
+                    None => elements.len() == names.len(),
                 };
+                if !fits {
+                    return Err(format!(
+                        "Cannot destructure an array of length {} into {}{} names",
+                        elements.len(),
+                        if rest.is_some() { "at least " } else { "" },
+                        names.len()
+                    ));
+                }
+
+                let mut elements = elements.into_iter();
+                for name in names {
+                    let val = elements.next().unwrap_or(Value::Null);
+                    self.assign_variable(name, val);
+                }
+                if let Some(rest) = rest {
+                    self.assign_variable(rest, Value::Array(elements.collect()));
+                }
+                Ok(None)
+            }
+            StmtKind::Let { name, value } => {
+                // Always declares in the innermost scope, shadowing outer variables
+                let val = self.evaluate_expr(value)?;
+                self.define_variable(name.clone(), val);
+                Ok(None)
+            }
+            StmtKind::FuncDecl { name, params, body, .. } => {
+                let func = self.function_value(params, body);
                 self.define_variable(name.clone(), func);
                 Ok(None)
             }
-            Stmt::Return(expr) => {
+            StmtKind::Return(expr) => {
                 let val = if let Some(e) = expr {
                     self.evaluate_expr(e)?
                 } else {
@@ -103,11 +440,11 @@ impl Interpreter {
                 };
                 Ok(Some(val))
             }
-            Stmt::Expr(expr) => {
+            StmtKind::Expr(expr) => {
                 self.evaluate_expr(expr)?;
                 Ok(None)
             }
-            Stmt::If { condition, then_branch, else_branch } => {
+            StmtKind::If { condition, then_branch, else_branch } => {
                 let cond_val = self.evaluate_expr(condition)?;
                 if cond_val.is_truthy() {
                     self.execute_stmt(then_branch)
@@ -117,7 +454,7 @@ impl Interpreter {
                     Ok(None)
                 }
             }
-            Stmt::While { condition, body } => {
+            StmtKind::While { condition, body } => {
                 while self.evaluate_expr(condition)?.is_truthy() {
                     if let Some(val) = self.execute_stmt(body)? {
                         return Ok(Some(val));
@@ -125,23 +462,58 @@ impl Interpreter {
                 }
                 Ok(None)
             }
-            Stmt::For { init, condition, increment, body } => {
+            StmtKind::Repeat { count, body } => {
+                let count = match self.evaluate_expr(count)? {
+                    Value::Number(n) if n >= 0.0 && n.fract() == 0.0 => n as u64,
+                    Value::Number(n) => return Err(format!("repeat expects a non-negative integer count, got {}", n)),
+                    other => return Err(format!("repeat expects a number, got {}", other.type_name())),
+                };
+                for _ in 0..count {
+                    if let Some(val) = self.execute_stmt(body)? {
+                        return Ok(Some(val));
+                    }
+                }
+                Ok(None)
+            }
+            StmtKind::For { init, condition, increment, body } => {
                 // Execute initializer
                 if let Some(init_stmt) = init {
                     self.execute_stmt(init_stmt)?;
                 }
+                let loop_vars: Vec<&String> = match init.as_ref().map(|stmt| &stmt.kind) {
+                    Some(StmtKind::VarDecl { name, .. } | StmtKind::Let { name, .. }) => vec![name],
+                    Some(StmtKind::Destructure { names, rest, .. }) => names.iter().chain(rest).collect(),
+                    _ => Vec::new(),
+                };
 
                 // Loop while condition is true
                 loop {
+                    // Each iteration works on a fresh copy of the loop variables, so
+                    // closures created in the body keep that iteration's values
+                    let bindings = loop_vars
+                        .iter()
+                        .filter_map(|name| Some((name.to_string(), self.get_variable(name).ok()?)))
+                        .collect();
+                    self.push_scope_with(bindings);
+
                     // Check condition
-                    if let Some(cond) = condition {
-                        if !self.evaluate_expr(cond)?.is_truthy() {
-                            break;
-                        }
-                    }
+                    let proceed = match condition {
+                        Some(cond) => self.evaluate_expr(cond)?.is_truthy(),
+                        None => true,
+                    };
 
                     // Execute body
-                    if let Some(val) = self.execute_stmt(body)? {
+                    let result = if proceed { self.execute_stmt(body)? } else { None };
+
+                    // Write this iteration's values back, where the next iteration
+                    // and the code after the loop see them
+                    for (name, value) in self.scopes.pop().unwrap_or_default() {
+                        self.set_variable(name, value);
+                    }
+                    if !proceed {
+                        break;
+                    }
+                    if let Some(val) = result {
                         return Ok(Some(val));
                     }
 
@@ -152,23 +524,30 @@ impl Interpreter {
                 }
                 Ok(None)
             }
-            Stmt::ForEach { variable, iterable, body } => {
+            StmtKind::ForEach { index, variable, iterable, body } => {
                 let iter_val = self.evaluate_expr(iterable)?;
                 
                 match iter_val {
                     Value::Array(items) => {
-                        for item in items {
+                        for (i, item) in items.into_iter().enumerate() {
+                            // Fresh binding per iteration for closures to capture
+                            self.push_scope();
+                            if let Some(index) = index {
+                                self.define_variable(index.clone(), Value::Number(i as f64));
+                            }
                             self.define_variable(variable.clone(), item);
-                            if let Some(val) = self.execute_stmt(body)? {
+                            let result = self.execute_stmt(body)?;
+                            self.pop_scope();
+                            if let Some(val) = result {
                                 return Ok(Some(val));
                             }
                         }
                         Ok(None)
                     }
-                    _ => Err(format!("Cannot iterate over non-array value in foreach loop"))
+                    _ => Err("Cannot iterate over non-array value in foreach loop".to_string())
                 }
             }
-            Stmt::Block(stmts) => {
+            StmtKind::Block(stmts) => {
                 self.push_scope();
                 let mut result = None;
                 for stmt in stmts {
@@ -180,20 +559,7 @@ impl Interpreter {
                 self.pop_scope();
                 Ok(result)
             }
-            Stmt::ClassDecl { name, extends, methods, properties } => {
-                // Build methods map
-                let mut methods_map = HashMap::new();
-                for (method_name, params, _return_type, body) in methods {
-                    methods_map.insert(method_name.clone(), (params.clone(), body.clone()));
-                }
-                
-                // Build properties map with defaults
-                let mut properties_map = HashMap::new();
-                for (prop_name, expr) in properties {
-                    let val = self.evaluate_expr(expr)?;
-                    properties_map.insert(prop_name.clone(), val);
-                }
-                
+            StmtKind::ClassDecl { name, extends, methods, properties } => {
                 // Get parent class if extending
                 let parent_value = if let Some(parent_name) = extends {
                     match self.get_variable(parent_name) {
@@ -203,12 +569,47 @@ impl Interpreter {
                 } else {
                     None
                 };
+
+                // Build methods map
+                let mut methods_map = HashMap::new();
+                let mut private = HashSet::new();
+                for method in methods {
+                    let overrides_parent = parent_value
+                        .as_ref()
+                        .is_some_and(|parent| parent.find_method(&method.name).is_some());
+                    if method.is_override && !overrides_parent {
+                        return Err(format!(
+                            "Method '{}' in class '{}' is marked override but no parent class defines it",
+                            method.name, name
+                        ));
+                    }
+                    methods_map.insert(method.name.clone(), (method.params.clone(), method.body.clone()));
+                    if method.is_private {
+                        private.insert(method.name.clone());
+                    }
+                }
+                
+                // Build properties map with defaults
+                let mut properties_map = HashMap::new();
+                let mut constants = HashSet::new();
+                for property in properties {
+                    let val = self.evaluate_expr(&property.value)?;
+                    properties_map.insert(property.name.clone(), val);
+                    if property.is_private {
+                        private.insert(property.name.clone());
+                    }
+                    if property.is_const {
+                        constants.insert(property.name.clone());
+                    }
+                }
                 
                 let class_value = Value::Class {
                     name: name.clone(),
                     parent: parent_value,
                     methods: methods_map,
                     properties: properties_map,
+                    private,
+                    constants,
                 };
                 
                 self.define_variable(name.clone(), class_value);
@@ -218,9 +619,21 @@ impl Interpreter {
     }
 
     pub fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, String> {
+        self.count(|c| &c.expressions);
         match expr {
             Expr::Literal(lit) => Ok(self.literal_to_value(lit)),
-            Expr::Variable(name) => self.get_variable(name),
+            Expr::Template(parts) => {
+                let mut result = String::new();
+                for part in parts {
+                    let value = self.evaluate_expr(part)?;
+                    result.push_str(&self.display_value(&value)?);
+                }
+                Ok(Value::String(result))
+            }
+            Expr::Variable(name) => match self.get_variable(name) {
+                Err(_) if self.undefined_is_null => Ok(Value::Null),
+                result => result,
+            },
             Expr::Assign { name, value } => {
                 let val = self.evaluate_expr(value)?;
                 self.set_variable(name.clone(), val.clone());
@@ -232,10 +645,7 @@ impl Interpreter {
                 
                 match obj_val {
                     Value::Object { class_name, mut properties } => {
-                        // Check if property is private and we're not in a method
-                        if property.starts_with("_") && !self.in_context {
-                            return Err(format!("Cannot assign private property '{}' from outside class", property));
-                        }
+                        self.check_property_assign(object, &class_name, property)?;
                         properties.insert(property.clone(), val.clone());
                         // Update the object in scope
                         if let Expr::Variable(var_name) = &**object {
@@ -246,8 +656,32 @@ impl Interpreter {
                     _ => Err(format!("Cannot assign property to {}", obj_val.type_name())),
                 }
             }
+            Expr::Index { .. } | Expr::PropertyAccess { .. } | Expr::MethodCall { .. } => {
+                Ok(self.evaluate_link(expr)?.unwrap_or(Value::Null))
+            }
+            Expr::IndexAssign { object, index, operator, value } => {
+                let container = self.evaluate_expr(object)?;
+                // Evaluated once, even for compound assignment
+                let index = self.evaluate_expr(index)?;
+                let mut val = self.evaluate_expr(value)?;
+                if let Some(op) = operator {
+                    let current = self.index_value(&container, &index)?;
+                    val = self.apply_binary_op(&current, op, &val)?;
+                }
+                let updated = self.set_index(container, &index, val.clone())?;
+                self.store(object, updated)?;
+                Ok(val)
+            }
             Expr::BinaryOp { left, operator, right } => {
                 let left_val = self.evaluate_expr(left)?;
+                // The right side of `&&`, `||`, and `??` is only evaluated when it
+                // can change the result
+                match operator {
+                    BinaryOp::And if !left_val.is_truthy() => return Ok(Value::Boolean(false)),
+                    BinaryOp::Or if left_val.is_truthy() => return Ok(Value::Boolean(true)),
+                    BinaryOp::Coalesce if left_val != Value::Null => return Ok(left_val),
+                    _ => {}
+                }
                 let right_val = self.evaluate_expr(right)?;
                 self.apply_binary_op(&left_val, operator, &right_val)
             }
@@ -258,6 +692,7 @@ impl Interpreter {
             Expr::FunctionCall { name, args } => {
                 self.call_function(name, args)
             }
+            Expr::Function { params, body } => Ok(self.function_value(params, body)),
             Expr::Lambda { params, body } => {
                 let closure = self.capture_closure();
                 Ok(Value::Lambda {
@@ -273,118 +708,105 @@ impl Interpreter {
             Expr::Array(elements) => {
                 let mut arr = Vec::new();
                 for elem in elements {
-                    arr.push(self.evaluate_expr(elem)?);
+                    if let Expr::Spread(inner) = elem {
+                        match self.evaluate_expr(inner)? {
+                            Value::Array(items) => arr.extend(items),
+                            other => return Err(format!("Cannot spread {} into an array", other.type_name())),
+                        }
+                    } else {
+                        arr.push(self.evaluate_expr(elem)?);
+                    }
                 }
                 Ok(Value::Array(arr))
             }
-            Expr::New { class_name, args: _ } => {
+            Expr::Spread(_) => Err("'...' can only be used inside an array literal".to_string()),
+            Expr::Map(entries) => {
+                let mut map = HashMap::new();
+                for (key, value) in entries {
+                    let val = self.evaluate_expr(value)?;
+                    map.insert(key.clone(), val);
+                }
+                Ok(Value::Map(map))
+            }
+            Expr::New { class_name, args, fields } => {
                 // Check if this is a private class and we're not in context
                 if class_name.starts_with("_") && !self.in_context {
                     return Err(format!("Cannot instantiate private class '{}' from outside context", class_name));
                 }
 
                 match self.get_variable(class_name) {
-                    Ok(Value::Class { properties, parent, .. }) => {
-                        // Start with parent properties if extending
-                        let mut obj_props = HashMap::new();
-                        
-                        if let Some(parent_class) = parent {
-                            if let Value::Class { properties: parent_props, .. } = &*parent_class {
-                                obj_props = parent_props.clone();
+                    Ok(class @ Value::Class { .. }) => {
+                        let mut properties = class.default_properties();
+                        // Field initializers take the constructor's place
+                        if let Some(fields) = fields {
+                            for (name, value) in fields {
+                                if !properties.contains_key(name) {
+                                    return Err(format!("Class '{}' has no property '{}'", class_name, name));
+                                }
+                                if name.starts_with("_") && !self.in_context {
+                                    return Err(format!("Cannot assign private property '{}' from outside class", name));
+                                }
+                                self.check_private(class_name, name, "assign private property")?;
+                                let val = self.evaluate_expr(value)?;
+                                properties.insert(name.clone(), val);
                             }
+                            return Ok(Value::Object { class_name: class_name.clone(), properties });
                         }
-                        
-                        // Override with own properties
-                        for (name, val) in &properties {
-                            obj_props.insert(name.clone(), val.clone());
-                        }
-                        
-                        Ok(Value::Object {
+
+                        let object = Value::Object {
                             class_name: class_name.clone(),
-                            properties: obj_props,
-                        })
+                            properties,
+                        };
+                        if class.find_method("init").is_none() {
+                            return Ok(object);
+                        }
+
+                        // Run the constructor and keep the object it set up
+                        let mut arg_values = Vec::new();
+                        for arg in args {
+                            arg_values.push(self.evaluate_expr(arg)?);
+                        }
+                        let (_, object) = self.call_method(object, "init", arg_values)?;
+                        Ok(object)
                     }
                     _ => Err(format!("Class '{}' not found", class_name)),
                 }
             }
-            Expr::PropertyAccess { object, property } => {
-                let obj_val = self.evaluate_expr(object)?;
-                match obj_val {
-                    Value::Object { properties, .. } => {
-                        // Check if property is private and we're not in a method
-                        if property.starts_with("_") && !self.in_context {
-                            return Err(format!("Cannot access private property '{}' from outside class", property));
-                        }
-                        properties.get(property).cloned()
-                            .ok_or_else(|| format!("Property '{}' not found on object", property))
-                    }
-                    _ => Err(format!("Cannot access property '{}' on {}", property, obj_val.type_name())),
-                }
+            Expr::TypeCheck { value, type_name } => {
+                let val = self.evaluate_expr(value)?;
+                let matches = match &val {
+                    _ if val.type_name() == type_name => true,
+                    Value::Object { class_name, .. } => self
+                        .get_variable(class_name)
+                        .is_ok_and(|class| class.extends(type_name)),
+                    _ => false,
+                };
+                Ok(Value::Boolean(matches))
             }
-            Expr::MethodCall { object, method, args } => {
-                let obj_val = self.evaluate_expr(object)?;
-                match &obj_val {
-                    Value::Object { class_name, properties } => {
-                        // Look up method in class
-                        if let Ok(Value::Class { methods, .. }) = self.get_variable(class_name) {
-                            if let Some((params, body)) = methods.get(method) {
-                                // Call method with object as context
-                                let mut method_scope = HashMap::new();
-                                method_scope.insert("this".to_string(), obj_val.clone());
-                                
-                                // Add all properties from the object to the scope
-                                for (prop_name, prop_val) in properties {
-                                    method_scope.insert(prop_name.clone(), prop_val.clone());
-                                }
-                                
-                                for (i, param) in params.iter().enumerate() {
-                                    let arg_val = if i < args.len() {
-                                        self.evaluate_expr(&args[i])?
-                                    } else {
-                                        Value::Null
-                                    };
-                                    method_scope.insert(param.clone(), arg_val);
-                                }
-                                
-                                self.scopes.push(method_scope.clone());
-                                let old_in_context = self.in_context;
-                                self.in_context = true; // Set flag to indicate we're in a method
-                                let mut result = Value::Null;
-                                for stmt in body {
-                                    if let Some(val) = self.execute_stmt(stmt)? {
-                                        result = val;
-                                        break;
-                                    }
-                                }
-                                self.in_context = old_in_context; // Restore the flag
-                                // Update object properties if they were modified
-                                let updated_scope = self.scopes.pop().unwrap();
-                                let mut updated_props = properties.clone();
-                                for (name, val) in &updated_scope {
-                                    if name != "this" && !params.contains(name) {
-                                        updated_props.insert(name.clone(), val.clone());
-                                    }
-                                }
-                                
-                                // Update the object in scope if it came from a variable
-                                if let Expr::Variable(var_name) = &**object {
-                                    let updated_object = Value::Object {
-                                        class_name: class_name.clone(),
-                                        properties: updated_props,
-                                    };
-                                    self.set_variable(var_name.clone(), updated_object);
-                                }
-                                
-                                Ok(result)
-                            } else {
-                                Err(format!("Method '{}' not found on class '{}'", method, class_name))
-                            }
-                        } else {
-                            Err(format!("Class '{}' not found", class_name))
-                        }
-                    }
-                    _ => Err(format!("Cannot call method on {}", obj_val.type_name())),
+            Expr::SuperCall { method, args } => {
+                let Some((Value::Class { name, parent, .. }, _)) = self.method_frames.last() else {
+                    return Err("'super' can only be used inside a method".to_string());
+                };
+                let Some(parent) = parent.as_deref() else {
+                    return Err(format!("Class '{}' has no parent class", name));
+                };
+
+                // `super(args)` runs the parent's constructor, which is optional
+                let method_name = method.as_deref().unwrap_or("init");
+                let owner = match parent.method_owner(method_name) {
+                    Some(owner) => owner.clone(),
+                    None if method.is_none() => return Ok(Value::Null),
+                    None => return Err(format!("Method '{}' not found on parent of class '{}'", method_name, name)),
+                };
+
+                let mut arg_values = Vec::new();
+                for arg in args {
+                    arg_values.push(self.evaluate_expr(arg)?);
                 }
+                let this = self.get_variable("this")?;
+                let (result, updated_this) = self.run_method(this, owner, method_name, arg_values)?;
+                self.set_variable("this".to_string(), updated_this);
+                Ok(result)
             }
         }
     }
@@ -398,7 +820,7 @@ impl Interpreter {
         }
     }
 
-    fn apply_binary_op(&self, left: &Value, op: &BinaryOp, right: &Value) -> Result<Value, String> {
+    fn apply_binary_op(&mut self, left: &Value, op: &BinaryOp, right: &Value) -> Result<Value, String> {
         match op {
             BinaryOp::Add => match (left, right) {
                 (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
@@ -406,82 +828,313 @@ impl Interpreter {
                 _ => Err(format!("Cannot add {} and {}", left.type_name(), right.type_name())),
             },
             BinaryOp::Subtract => {
-                let a = left.to_number()?;
-                let b = right.to_number()?;
+                let a = self.operand_number(left)?;
+                let b = self.operand_number(right)?;
                 Ok(Value::Number(a - b))
             }
             BinaryOp::Multiply => {
-                let a = left.to_number()?;
-                let b = right.to_number()?;
+                let a = self.operand_number(left)?;
+                let b = self.operand_number(right)?;
                 Ok(Value::Number(a * b))
             }
             BinaryOp::Divide => {
-                let a = left.to_number()?;
-                let b = right.to_number()?;
+                let a = self.operand_number(left)?;
+                let b = self.operand_number(right)?;
                 if b == 0.0 {
                     Err("Division by zero".to_string())
                 } else {
                     Ok(Value::Number(a / b))
                 }
             }
-            BinaryOp::Equal => Ok(Value::Boolean(self.values_equal(left, right))),
-            BinaryOp::NotEqual => Ok(Value::Boolean(!self.values_equal(left, right))),
-            BinaryOp::Less => {
-                let a = left.to_number()?;
-                let b = right.to_number()?;
-                Ok(Value::Boolean(a < b))
+            BinaryOp::Power => {
+                let a = self.operand_number(left)?;
+                let b = self.operand_number(right)?;
+                Ok(Value::Number(a.powf(b)))
+            }
+            BinaryOp::Equal => Ok(Value::Boolean(self.values_equal(left, right)?)),
+            BinaryOp::NotEqual => Ok(Value::Boolean(!self.values_equal(left, right)?)),
+            BinaryOp::Less => Ok(Value::Boolean(self.cmp_value(left, right)? == Ordering::Less)),
+            BinaryOp::LessEqual => Ok(Value::Boolean(self.cmp_value(left, right)? != Ordering::Greater)),
+            BinaryOp::Greater => Ok(Value::Boolean(self.cmp_value(left, right)? == Ordering::Greater)),
+            BinaryOp::GreaterEqual => Ok(Value::Boolean(self.cmp_value(left, right)? != Ordering::Less)),
+            BinaryOp::And => Ok(Value::Boolean(left.is_truthy() && right.is_truthy())),
+            BinaryOp::Or => Ok(Value::Boolean(left.is_truthy() || right.is_truthy())),
+            BinaryOp::Coalesce => Ok(if *left == Value::Null { right.clone() } else { left.clone() }),
+            BinaryOp::BitAnd => Ok(Value::Number((self.operand_integer(left, "&")? & self.operand_integer(right, "&")?) as f64)),
+            BinaryOp::BitOr => Ok(Value::Number((self.operand_integer(left, "|")? | self.operand_integer(right, "|")?) as f64)),
+            BinaryOp::BitXor => Ok(Value::Number((self.operand_integer(left, "^")? ^ self.operand_integer(right, "^")?) as f64)),
+            BinaryOp::In => match (left, right) {
+                (_, Value::Array(_)) => self.call_contains(vec![right.clone(), left.clone()]),
+                (Value::String(needle), Value::String(haystack)) => Ok(Value::Boolean(haystack.contains(needle.as_str()))),
+                (Value::String(_), Value::Map(_) | Value::Object { .. }) => self.call_has(vec![right.clone(), left.clone()]),
+                _ => Err(format!("Cannot check whether {} is in {}", left.type_name(), right.type_name())),
+            },
+            // Shifts are arithmetic, like `shl` and `shr`
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                let symbol = if *op == BinaryOp::ShiftLeft { "<<" } else { ">>" };
+                let a = self.operand_integer(left, symbol)?;
+                let n = self.operand_integer(right, symbol)?;
+                if !(0..64).contains(&n) {
+                    return Err(format!("'{}' expects a shift between 0 and 63, got {}", symbol, n));
+                }
+                Ok(Value::Number((if *op == BinaryOp::ShiftLeft { a << n } else { a >> n }) as f64))
             }
-            BinaryOp::LessEqual => {
-                let a = left.to_number()?;
-                let b = right.to_number()?;
-                Ok(Value::Boolean(a <= b))
+        }
+    }
+
+    /// `container[index]`: array elements and string characters by position, map
+    /// entries by key (null when missing)
+    fn index_value(&self, container: &Value, index: &Value) -> Result<Value, String> {
+        match (container, index) {
+            (Value::Array(arr), Value::Number(_)) => {
+                let i = self.element_index(index, arr.len())?;
+                Ok(arr[i].clone())
             }
-            BinaryOp::Greater => {
-                let a = left.to_number()?;
-                let b = right.to_number()?;
-                Ok(Value::Boolean(a > b))
+            (Value::String(s), Value::Number(_)) => {
+                let i = self.element_index(index, s.chars().count())?;
+                Ok(Value::String(s.chars().nth(i).unwrap().to_string()))
             }
-            BinaryOp::GreaterEqual => {
-                let a = left.to_number()?;
-                let b = right.to_number()?;
-                Ok(Value::Boolean(a >= b))
+            (Value::Map(map), Value::String(key)) => Ok(map.get(key).cloned().unwrap_or(Value::Null)),
+            (Value::Array(_) | Value::String(_), _) => {
+                Err(format!("{} index must be a number, got {}", container.type_name(), index.type_name()))
             }
-            BinaryOp::And => Ok(Value::Boolean(left.is_truthy() && right.is_truthy())),
-            BinaryOp::Or => Ok(Value::Boolean(left.is_truthy() || right.is_truthy())),
+            (Value::Map(_), _) => Err(format!("Map keys must be strings, got {}", index.type_name())),
+            _ => Err(format!("Cannot index into {}", container.type_name())),
+        }
+    }
+
+    /// Copy of `container` with `container[index]` replaced by `value`
+    fn set_index(&self, container: Value, index: &Value, value: Value) -> Result<Value, String> {
+        match (container, index) {
+            // Assigning just past the end appends; anything further is an error
+            // rather than silently padding the array with nulls
+            (Value::Array(mut arr), Value::Number(n)) if *n == arr.len() as f64 => {
+                arr.push(value);
+                Ok(Value::Array(arr))
+            }
+            (Value::Array(mut arr), Value::Number(_)) => {
+                let i = self.element_index(index, arr.len())?;
+                arr[i] = value;
+                Ok(Value::Array(arr))
+            }
+            (Value::Map(mut map), Value::String(key)) => {
+                map.insert(key.clone(), value);
+                Ok(Value::Map(map))
+            }
+            (Value::Array(_), _) => Err(format!("Array index must be a number, got {}", index.type_name())),
+            (Value::Map(_), _) => Err(format!("Map keys must be strings, got {}", index.type_name())),
+            (other, _) => Err(format!("Cannot assign to an index of {}", other.type_name())),
+        }
+    }
+
+    fn element_index(&self, index: &Value, len: usize) -> Result<usize, String> {
+        let n = index.to_number()?;
+        if n.fract() != 0.0 || n < 0.0 || n >= len as f64 {
+            return Err(format!("Index {} out of bounds for length {}", index, len));
+        }
+        Ok(n as usize)
+    }
+
+    /// `object.property`, refusing private properties outside the class
+    fn read_property(&self, class_name: &str, properties: &HashMap<String, Value>, property: &str) -> Result<Value, String> {
+        // Check if property is private and we're not in a method
+        if property.starts_with("_") && !self.in_context {
+            return Err(format!("Cannot access private property '{}' from outside class", property));
+        }
+        self.check_private(class_name, property, "access private property")?;
+        properties.get(property).cloned()
+            .ok_or_else(|| format!("Property '{}' not found on object", property))
+    }
+
+    /// Write an updated value back to the variable, property, or element an
+    /// assignment target refers to. Other targets are temporaries, so there is
+    /// nothing to update
+    fn store(&mut self, target: &Expr, value: Value) -> Result<(), String> {
+        match target {
+            Expr::Variable(name) => {
+                self.set_variable(name.clone(), value);
+                Ok(())
+            }
+            Expr::PropertyAccess { object, property, .. } => match self.evaluate_expr(object)? {
+                Value::Object { class_name, mut properties } => {
+                    self.check_property_assign(object, &class_name, property)?;
+                    properties.insert(property.clone(), value);
+                    self.store(object, Value::Object { class_name, properties })
+                }
+                other => Err(format!("Cannot assign property to {}", other.type_name())),
+            },
+            Expr::Index { object, index } => {
+                let container = self.evaluate_expr(object)?;
+                let index = self.evaluate_expr(index)?;
+                let updated = self.set_index(container, &index, value)?;
+                self.store(object, updated)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reject assigning a property from outside its class when it is private, and
+    /// reassigning a `const` property once the object's constructor has finished
+    fn check_property_assign(&self, object: &Expr, class_name: &str, property: &str) -> Result<(), String> {
+        let constructing = matches!(object, Expr::Variable(name) if name == "this")
+            && self.method_frames.last().is_some_and(|(_, method)| method == "init");
+        self.check_property_write(class_name, property, constructing)
+    }
+
+    /// The private and const rules for anything that sets a property, including
+    /// `with` and `merge`; `const` properties can only be set while constructing
+    fn check_property_write(&self, class_name: &str, property: &str, constructing: bool) -> Result<(), String> {
+        if property.starts_with("_") && !self.in_context {
+            return Err(format!("Cannot assign private property '{}' from outside class", property));
+        }
+        self.check_private(class_name, property, "assign private property")?;
+
+        match self.get_variable(class_name) {
+            Ok(class) if class.is_const(property) && !constructing => Err(format!(
+                "Cannot assign to const property '{}' of class '{}'",
+                property, class_name
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Members declared `private` can only be used from methods of the declaring
+    /// class itself, not its subclasses. `action` describes the attempt for the
+    /// error message
+    fn check_private(&self, class_name: &str, member: &str, action: &str) -> Result<(), String> {
+        let Ok(class) = self.get_variable(class_name) else {
+            return Ok(());
+        };
+        let Some(owner) = class.private_owner(member) else {
+            return Ok(());
+        };
+        let inside = self
+            .method_frames
+            .last()
+            .is_some_and(|(class, _)| matches!(class, Value::Class { name, .. } if name == owner));
+        if inside {
+            Ok(())
+        } else {
+            Err(format!("Cannot {} '{}' of class '{}' from outside the class", action, member, owner))
+        }
+    }
+
+    /// Order two values: strings lexicographically, booleans with `false < true`,
+    /// anything else numerically. Strict mode only orders values of the same type
+    fn cmp_value(&self, left: &Value, right: &Value) -> Result<Ordering, String> {
+        match (left, right) {
+            (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+            (Value::Boolean(a), Value::Boolean(b)) => Ok(a.cmp(b)),
+            (Value::Number(_), Value::Number(_)) => self.cmp_numbers(left, right),
+            _ if self.strict => Err(format!("Cannot compare {} and {}", left.type_name(), right.type_name())),
+            _ => self.cmp_numbers(left, right),
         }
     }
 
+    fn cmp_numbers(&self, left: &Value, right: &Value) -> Result<Ordering, String> {
+        let a = left.to_number()?;
+        let b = right.to_number()?;
+        a.partial_cmp(&b)
+            .ok_or_else(|| format!("Cannot compare {} and {}", left, right))
+    }
+
+    /// Convert an operator operand to a number, coercing only in loose mode
+    fn operand_number(&self, value: &Value) -> Result<f64, String> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            _ if self.strict => Err(format!(
+                "Cannot implicitly convert {} to Number in strict mode",
+                value.type_name()
+            )),
+            _ => value.to_number(),
+        }
+    }
+
+    /// An operand of a bitwise operator, which must be integer-valued
+    fn operand_integer(&self, value: &Value, symbol: &str) -> Result<i64, String> {
+        let n = self.operand_number(value)?;
+        if n.fract() != 0.0 || !n.is_finite() {
+            return Err(format!("'{}' expects integer operands, got {}", symbol, n));
+        }
+        Ok(n as i64)
+    }
+
     fn apply_unary_op(&self, op: &UnaryOp, val: &Value) -> Result<Value, String> {
         match op {
             UnaryOp::Not => Ok(Value::Boolean(!val.is_truthy())),
             UnaryOp::Negate => {
-                let n = val.to_number()?;
+                let n = self.operand_number(val)?;
                 Ok(Value::Number(-n))
             }
+            UnaryOp::BitNot => Ok(Value::Number(!self.operand_integer(val, "~")? as f64)),
         }
     }
 
-    fn values_equal(&self, a: &Value, b: &Value) -> bool {
+    /// Equality used by `==`, `match`, and the array search builtins. An object
+    /// whose class defines `__eq__(other)` decides for itself (the left operand is
+    /// asked first); everything else compares structurally
+    fn values_equal(&mut self, a: &Value, b: &Value) -> Result<bool, String> {
+        for (object, other) in [(a, b), (b, a)] {
+            let Value::Object { class_name, .. } = object else {
+                continue;
+            };
+            let has_eq = matches!(
+                self.get_variable(class_name),
+                Ok(class) if class.find_method("__eq__").is_some()
+            );
+            if has_eq {
+                return match self.call_method(object.clone(), "__eq__", vec![other.clone()])?.0 {
+                    Value::Boolean(equal) => Ok(equal),
+                    result => Err(format!("__eq__ on '{}' must return a Boolean, got {}", class_name, result.type_name())),
+                };
+            }
+        }
+
         match (a, b) {
-            (Value::Number(x), Value::Number(y)) => x == y,
-            (Value::String(x), Value::String(y)) => x == y,
-            (Value::Boolean(x), Value::Boolean(y)) => x == y,
-            (Value::Null, Value::Null) => true,
-            _ => false,
+            (Value::Number(x), Value::Number(y)) => Ok(x == y),
+            (Value::String(x), Value::String(y)) => Ok(x == y),
+            (Value::Boolean(x), Value::Boolean(y)) => Ok(x == y),
+            (Value::Null, Value::Null) => Ok(true),
+            (Value::Array(x), Value::Array(y)) => {
+                if x.len() != y.len() {
+                    return Ok(false);
+                }
+                for (a, b) in x.iter().zip(y) {
+                    if !self.values_equal(a, b)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            (Value::Map(x), Value::Map(y)) => self.maps_equal(x, y),
+            (
+                Value::Object { class_name: class_x, properties: x },
+                Value::Object { class_name: class_y, properties: y },
+            ) => Ok(class_x == class_y && self.maps_equal(x, y)?),
+            // Functions and classes are never equal
+            _ => Ok(false),
         }
     }
 
+    fn maps_equal(&mut self, a: &HashMap<String, Value>, b: &HashMap<String, Value>) -> Result<bool, String> {
+        if a.len() != b.len() {
+            return Ok(false);
+        }
+        for (key, x) in a {
+            match b.get(key) {
+                Some(y) if self.values_equal(x, y)? => {}
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
     fn call_function(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
         // Check if this is a private function and we're not in context
         if name.starts_with("_") && !self.in_context {
             return Err(format!("Cannot call private function '{}' from outside context", name));
         }
 
-        // First check if it's a method call (first arg is the object)
-        if name == "map" && !args.is_empty() {
-            return self.call_map_method(args);
-        }
-
         // Evaluate arguments
         let mut arg_values = Vec::new();
         for arg in args {
@@ -490,11 +1143,24 @@ impl Interpreter {
 
         // Get function value
         let func = self.get_variable(name)?;
+        self.call_value(name, func, arg_values)
+    }
 
+    /// Call a function value with already evaluated arguments; `name` is only used in errors
+    fn call_value(&mut self, name: &str, func: Value, arg_values: Vec<Value>) -> Result<Value, String> {
+        self.count(|c| &c.calls);
         match func {
             Value::Function { params, body, closure } => {
-                if params.len() != arg_values.len() {
-                    return Err(format!("Function {} expects {} arguments, got {}", name, params.len(), arg_values.len()));
+                let required = params.iter().filter(|p| p.default.is_none() && !p.rest).count();
+                let arity = if params.last().is_some_and(|p| p.rest) {
+                    Arity::AtLeast(required)
+                } else if required == params.len() {
+                    Arity::Exact(required)
+                } else {
+                    Arity::Range(required, params.len())
+                };
+                if !arity.accepts(arg_values.len()) {
+                    return Err(format!("Function {} expects {} arguments, got {}", name, arity, arg_values.len()));
                 }
 
                 self.push_scope();
@@ -504,10 +1170,7 @@ impl Interpreter {
                     self.define_variable(name, value);
                 }
 
-                // Bind parameters
-                for (param, arg) in params.iter().zip(arg_values.iter()) {
-                    self.define_variable(param.clone(), arg.clone());
-                }
+                self.bind_params(&params, arg_values)?;
 
                 // Execute body with context flag set
                 let old_in_context = self.in_context;
@@ -551,82 +1214,858 @@ impl Interpreter {
                 Ok(result)
             }
             Value::NativeFunction { name, arity } => {
-                if arity != arg_values.len() {
+                if !arity.accepts(arg_values.len()) {
                     return Err(format!("Native function {} expects {} arguments, got {}", name, arity, arg_values.len()));
                 }
-                builtins::call_builtin(&name, arg_values)
+                self.call_native(&name, arg_values)
             }
             _ => Err(format!("{} is not a function", name)),
         }
     }
 
-    fn call_map_method(&mut self, args: &[Expr]) -> Result<Value, String> {
-        if args.len() != 2 {
-            return Err(format!("map expects 2 arguments (array, function), got {}", args.len()));
+    /// Run `method` with `object` as its receiver, returning the method's result and
+    /// the object with any property changes the method made
+    fn call_method(&mut self, object: Value, method: &str, args: Vec<Value>) -> Result<(Value, Value), String> {
+        let Value::Object { class_name, .. } = &object else {
+            return Err(format!("Cannot call method on {}", object.type_name()));
+        };
+        // The most derived definition wins; otherwise walk up the parent classes
+        let owner = match self.get_variable(class_name) {
+            Ok(class @ Value::Class { .. }) => match class.method_owner(method) {
+                Some(owner) => owner.clone(),
+                None => return Err(format!("Method '{}' not found on class '{}'", method, class_name)),
+            },
+            _ => return Err(format!("Class '{}' not found", class_name)),
+        };
+        self.run_method(object, owner, method, args)
+    }
+
+    /// Define parameters in the current scope. Parameters without an argument take
+    /// their default, evaluated after the earlier parameters are bound, or null; a
+    /// rest parameter gets an array of the remaining arguments
+    fn bind_params(&mut self, params: &[Param], args: Vec<Value>) -> Result<(), String> {
+        let mut args = args.into_iter();
+        for param in params {
+            if param.rest {
+                self.define_variable(param.name.clone(), Value::Array(args.by_ref().collect()));
+                continue;
+            }
+            let value = match (args.next(), &param.default) {
+                (Some(arg), _) => arg,
+                (None, Some(default)) => self.evaluate_expr(default)?,
+                (None, None) => Value::Null,
+            };
+            self.define_variable(param.name.clone(), value);
         }
+        Ok(())
+    }
 
-        let array_val = self.evaluate_expr(&args[0])?;
-        let func_val = self.evaluate_expr(&args[1])?;
+    /// Run a method defined on `owner` with `object` as its receiver
+    fn run_method(&mut self, object: Value, owner: Value, method: &str, args: Vec<Value>) -> Result<(Value, Value), String> {
+        self.count(|c| &c.calls);
+        let Value::Object { class_name, properties } = &object else {
+            return Err(format!("Cannot call method on {}", object.type_name()));
+        };
+        let (params, body) = match owner.find_method(method) {
+            Some(found) => found.clone(),
+            None => return Err(format!("Method '{}' not found on class '{}'", method, class_name)),
+        };
 
-        if let Value::Array(arr) = array_val {
-            let mut result = Vec::new();
-            
-            for item in arr {
-                match &func_val {
-                    Value::Lambda { params, body, closure } => {
-                        if params.len() != 1 {
-                            return Err("map callback expects 1 parameter".to_string());
-                        }
+        // The receiver scope holds `this` and the object's properties as plain
+        // variables; parameters and locals live in a scope above it. Properties
+        // another class in the hierarchy declares `private` are left out
+        let owner_name = match &owner {
+            Value::Class { name, .. } => name.clone(),
+            _ => String::new(),
+        };
+        let class = self.get_variable(class_name).ok();
+        let (mut receiver_scope, hidden): (HashMap<_, _>, HashMap<_, _>) =
+            properties.clone().into_iter().partition(|(name, _)| {
+                class.as_ref().and_then(|c| c.private_owner(name)).is_none_or(|o| o == owner_name)
+            });
+        receiver_scope.insert("this".to_string(), object.clone());
+        self.push_scope_with(receiver_scope);
 
-                        self.push_scope();
+        self.push_scope();
+        self.bind_params(&params, args)?;
 
-                        // Restore closure
-                        for (name, value) in closure {
-                            self.define_variable(name.clone(), value.clone());
+        let old_in_context = self.in_context;
+        self.in_context = true; // Set flag to indicate we're in a method
+        self.method_frames.push((owner, method.to_string()));
+        let mut result = Value::Null;
+        for stmt in &body {
+            if let Some(val) = self.execute_stmt(stmt)? {
+                result = val;
+                break;
+            }
+        }
+        self.method_frames.pop();
+        self.in_context = old_in_context; // Restore the flag
+        self.scopes.pop();
+
+        // Update object properties if they were modified
+        let mut updated_props = self.scopes.pop().unwrap();
+        // Hidden properties only change through `this`, e.g. by calling one of
+        // the declaring class's methods
+        if let Some(Value::Object { properties: latest, .. }) = updated_props.remove("this") {
+            for (name, value) in hidden {
+                let value = latest.get(&name).cloned().unwrap_or(value);
+                updated_props.insert(name, value);
+            }
+        }
+
+        // Methods see properties as plain variables, so `const` is checked afterwards
+        if method != "init" {
+            if let Ok(class) = self.get_variable(class_name) {
+                let changed = updated_props.iter().find(|(name, value)| {
+                    class.is_const(name) && properties.get(*name) != Some(*value)
+                });
+                if let Some((name, _)) = changed {
+                    return Err(format!("Cannot assign to const property '{}' of class '{}'", name, class_name));
+                }
+            }
+        }
+
+        let updated_object = Value::Object {
+            class_name: class_name.clone(),
+            properties: updated_props,
+        };
+        Ok((result, updated_object))
+    }
+
+    /// Builtins that need the interpreter (callbacks, equality, ordering) are handled
+    /// here; everything else goes to `builtins::call_builtin`
+    fn call_native(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        if let Some(native) = self.natives.get(name) {
+            return native(args);
+        }
+        match name {
+            "with" => self.call_with(args),
+            // Merging objects overwrites the properties that differ, so those
+            // follow the rules for assigning them
+            "merge" => {
+                if let [Value::Object { class_name, properties: a }, Value::Object { properties: b, .. }] = &args[..] {
+                    for (property, value) in b {
+                        if a.get(property) != Some(value) {
+                            self.check_property_write(class_name, property, false)?;
                         }
+                    }
+                }
+                builtins::call_builtin(name, args)
+            }
+            "print" | "println" => {
+                let mut texts = Vec::with_capacity(args.len());
+                for arg in &args {
+                    texts.push(match arg {
+                        Value::Array(_) | Value::Map(_) => self.format_value(arg),
+                        other => self.display_value(other)?,
+                    });
+                }
+                println!("{}", texts.join(" "));
+                Ok(Value::Null)
+            }
+            "input" => {
+                if let Some(prompt) = args.first() {
+                    print!("{}", self.display_value(prompt)?);
+                    io::stdout().flush().map_err(|e| format!("Cannot write prompt: {}", e))?;
+                }
+                self.read_input_line()
+            }
+            "print_depth" => {
+                self.print_depth = match &args[0] {
+                    Value::Null => None,
+                    depth => match depth.to_number()? {
+                        n if n >= 0.0 && n.fract() == 0.0 => Some(n as usize),
+                        n => return Err(format!("print_depth expects a non-negative integer or null, got {}", n)),
+                    },
+                };
+                Ok(Value::Null)
+            }
+            "to_string" => Ok(Value::String(self.display_value(&args[0])?)),
+            "map" => self.call_map(args),
+            "filter" => self.call_filter(args),
+            "forEach" => self.call_for_each(args),
+            "zip_with" => self.call_zip_with(args),
+            "find" => Ok(self.find_first("find", args, true)?.map_or(Value::Null, |(_, item)| item)),
+            "find_index" => Ok(Value::Number(self.find_first("find_index", args, true)?.map_or(-1.0, |(i, _)| i as f64))),
+            "any" => Ok(Value::Boolean(self.find_first("any", args, true)?.is_some())),
+            "all" => Ok(Value::Boolean(self.find_first("all", args, false)?.is_none())),
+            "take_while" | "drop_while" => self.call_take_while(name, args),
+            "reduce" => self.call_reduce(args),
+            "sort" => self.call_sort(args),
+            "sort_by_key" => self.call_sort_by_key(args),
+            "contains" => self.call_contains(args),
+            "index_of" => self.call_index_of(args),
+            "to_json" => self.call_to_json(args),
+            "keys" | "own_keys" | "values" | "entries" => self.call_keys(name, args),
+            "has" => self.call_has(args),
+            "methods" => self.call_methods(args),
+            "properties" => match &args[0] {
+                Value::Object { .. } => self.call_keys("keys", args),
+                other => Err(format!("properties expects an Object, got {}", other.type_name())),
+            },
+            "force" => self.call_force(args),
+            "random" | "random_int" | "seed" => self.call_random(name, args),
+            _ => builtins::call_builtin(name, args),
+        }
+    }
 
-                        // Bind parameter
-                        self.define_variable(params[0].clone(), item);
+    /// Text shown for a result by `print` and the REPL, which respects the
+    /// `print_depth` limit
+    pub fn format_value(&self, value: &Value) -> String {
+        match self.print_depth {
+            Some(depth) => value.to_string_with_depth(depth),
+            None => value.to_string(),
+        }
+    }
+
+    /// Text shown for a value by `print`, `to_string`, and string interpolation:
+    /// objects whose class defines a `toString()` method are shown as its result
+    fn display_value(&mut self, value: &Value) -> Result<String, String> {
+        if let Value::Object { class_name, .. } = value {
+            let has_to_string = matches!(
+                self.get_variable(class_name),
+                Ok(class) if class.find_method("toString").is_some_and(|(params, _)| params.is_empty())
+            );
+            if has_to_string {
+                return match self.call_method(value.clone(), "toString", Vec::new())?.0 {
+                    Value::String(s) => Ok(s),
+                    other => Err(format!("toString on '{}' must return a String, got {}", class_name, other.type_name())),
+                };
+            }
+        }
+        Ok(value.to_string())
+    }
+
+    /// The value of an index, property access or method call, or None when an
+    /// optional link found null. That cuts the rest of the chain short too, so
+    /// with `a` null, `a?.b.c` is null rather than an error
+    fn evaluate_link(&mut self, expr: &Expr) -> Result<Option<Value>, String> {
+        match expr {
+            Expr::Index { object, index } => {
+                let Some(container) = self.evaluate_chain(object)? else {
+                    return Ok(None);
+                };
+                let index = self.evaluate_expr(index)?;
+                self.index_value(&container, &index).map(Some)
+            }
+            Expr::PropertyAccess { object, property, optional } => {
+                let Some(obj_val) = self.evaluate_chain(object)? else {
+                    return Ok(None);
+                };
+                match obj_val {
+                    Value::Null if *optional => Ok(None),
+                    Value::Object { class_name, properties } => self.read_property(&class_name, &properties, property).map(Some),
+                    _ => Err(format!("Cannot access property '{}' on {}", property, obj_val.type_name())),
+                }
+            }
+            Expr::MethodCall { object, method, args, optional } => {
+                let Some(obj_val) = self.evaluate_chain(object)? else {
+                    return Ok(None);
+                };
+                match &obj_val {
+                    // The arguments aren't evaluated either
+                    Value::Null if *optional => Ok(None),
+                    Value::Object { class_name, .. } => {
+                        self.check_private(class_name, method, "call private method")?;
+                        let mut arg_values = Vec::new();
+                        for arg in args {
+                            arg_values.push(self.evaluate_expr(arg)?);
+                        }
+                        let (result, updated_object) = self.call_method(obj_val.clone(), method, arg_values)?;
 
-                        // Evaluate body
-                        let val = self.evaluate_expr(body)?;
-                        result.push(val);
+                        // Update the object in scope if it came from a variable
+                        if let Expr::Variable(var_name) = &**object {
+                            self.set_variable(var_name.clone(), updated_object);
+                        }
 
-                        self.pop_scope();
+                        Ok(Some(result))
                     }
-                    _ => return Err("map expects a function as second argument".to_string()),
+                    Value::Array(_) => {
+                        let mut arg_values = Vec::new();
+                        for arg in args {
+                            arg_values.push(self.evaluate_expr(arg)?);
+                        }
+                        self.call_array_method(obj_val.clone(), method, arg_values).map(Some)
+                    }
+                    _ => Err(format!("Cannot call method on {}", obj_val.type_name())),
                 }
             }
+            _ => self.evaluate_expr(expr).map(Some),
+        }
+    }
+
+    /// Like `evaluate_expr`, but a link keeps the chain's None
+    fn evaluate_chain(&mut self, expr: &Expr) -> Result<Option<Value>, String> {
+        match expr {
+            Expr::Index { .. } | Expr::PropertyAccess { .. } | Expr::MethodCall { .. } => {
+                self.count(|c| &c.expressions);
+                self.evaluate_link(expr)
+            }
+            _ => self.evaluate_expr(expr).map(Some),
+        }
+    }
+
+    /// `array.method(args)` is the same call as `method(array, args)`
+    fn call_array_method(&mut self, array: Value, method: &str, args: Vec<Value>) -> Result<Value, String> {
+        if !ARRAY_METHODS.contains(&method) {
+            return Err(format!("Method '{}' not found on Array", method));
+        }
+        let mut arg_values = vec![array];
+        arg_values.extend(args);
+        if let Some(arity) = self.array_method_arities.get(method) {
+            if !arity.accepts(arg_values.len()) {
+                return Err(format!("Native function {} expects {} arguments, got {}", method, arity, arg_values.len()));
+            }
+        }
+        self.call_native(method, arg_values)
+    }
+
+    fn call_with(&self, args: Vec<Value>) -> Result<Value, String> {
+        match (&args[0], &args[1]) {
+            (Value::Object { class_name, properties }, Value::Map(overrides)) => {
+                let mut updated_props = properties.clone();
+                for (name, val) in overrides {
+                    // Overriding a property follows the same rules as assigning it
+                    self.check_property_write(class_name, name, false)?;
+                    updated_props.insert(name.clone(), val.clone());
+                }
+                Ok(Value::Object {
+                    class_name: class_name.clone(),
+                    properties: updated_props,
+                })
+            }
+            (Value::Map(map), Value::Map(overrides)) => {
+                let mut updated = map.clone();
+                for (key, val) in overrides {
+                    updated.insert(key.clone(), val.clone());
+                }
+                Ok(Value::Map(updated))
+            }
+            _ => Err(format!(
+                "with expects an object and a map of overrides, got {} and {}",
+                args[0].type_name(),
+                args[1].type_name()
+            )),
+        }
+    }
 
+    fn call_map(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!("map expects 2 arguments (array, function), got {}", args.len()));
+        }
+
+        let mut args = args.into_iter();
+        let (array_val, func_val) = (args.next().unwrap(), args.next().unwrap());
+        if let Value::Array(arr) = array_val {
+            let mut result = Vec::new();
+            for item in arr {
+                result.push(self.call_value("map callback", func_val.clone(), vec![item])?);
+            }
             Ok(Value::Array(result))
         } else {
             Err(format!("map expects an array, got {}", array_val.type_name()))
         }
     }
 
+    /// Call the function on each element for its side effects
+    fn call_for_each(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!("forEach expects 2 arguments (array, function), got {}", args.len()));
+        }
+
+        let mut args = args.into_iter();
+        let (array_val, func_val) = (args.next().unwrap(), args.next().unwrap());
+        if let Value::Array(arr) = array_val {
+            for item in arr {
+                self.call_value("forEach callback", func_val.clone(), vec![item])?;
+            }
+            Ok(Value::Null)
+        } else {
+            Err(format!("forEach expects an array, got {}", array_val.type_name()))
+        }
+    }
+
+    /// Combine elements at the same position with a two-argument function,
+    /// stopping at the end of the shorter array
+    fn call_zip_with(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 3 {
+            return Err(format!("zip_with expects 3 arguments (array, array, function), got {}", args.len()));
+        }
+
+        let mut args = args.into_iter();
+        let (first, second, func_val) = (args.next().unwrap(), args.next().unwrap(), args.next().unwrap());
+        match (first, second) {
+            (Value::Array(a), Value::Array(b)) => {
+                let mut result = Vec::new();
+                for (x, y) in a.into_iter().zip(b) {
+                    result.push(self.call_value("zip_with callback", func_val.clone(), vec![x, y])?);
+                }
+                Ok(Value::Array(result))
+            }
+            (Value::Array(_), other) | (other, _) => {
+                Err(format!("zip_with expects two arrays, got {}", other.type_name()))
+            }
+        }
+    }
+
+    fn call_filter(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!("filter expects 2 arguments (array, function), got {}", args.len()));
+        }
+
+        let mut args = args.into_iter();
+        let (array_val, func_val) = (args.next().unwrap(), args.next().unwrap());
+        if let Value::Array(arr) = array_val {
+            let mut result = Vec::new();
+            for item in arr {
+                if self.call_value("filter callback", func_val.clone(), vec![item.clone()])?.is_truthy() {
+                    result.push(item);
+                }
+            }
+            Ok(Value::Array(result))
+        } else {
+            Err(format!("filter expects an array, got {}", array_val.type_name()))
+        }
+    }
+
+    /// The first element (and its index) for which the predicate's truthiness is
+    /// `wanted`; the predicate isn't called again once a match is found
+    fn find_first(&mut self, name: &str, args: Vec<Value>, wanted: bool) -> Result<Option<(usize, Value)>, String> {
+        if args.len() != 2 {
+            return Err(format!("{} expects 2 arguments (array, function), got {}", name, args.len()));
+        }
+
+        let mut args = args.into_iter();
+        let (array_val, func_val) = (args.next().unwrap(), args.next().unwrap());
+        if let Value::Array(arr) = array_val {
+            let callback = format!("{} callback", name);
+            for (i, item) in arr.into_iter().enumerate() {
+                if self.call_value(&callback, func_val.clone(), vec![item.clone()])?.is_truthy() == wanted {
+                    return Ok(Some((i, item)));
+                }
+            }
+            Ok(None)
+        } else {
+            Err(format!("{} expects an array, got {}", name, array_val.type_name()))
+        }
+    }
+
+    /// `take_while` keeps the leading run of elements the predicate accepts and
+    /// `drop_while` the rest; the predicate isn't called past the first rejection
+    fn call_take_while(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let mut items = match &args[..] {
+            [Value::Array(arr), _] => arr.clone(),
+            _ => Vec::new(),
+        };
+        let end = self.find_first(name, args, false)?.map_or(items.len(), |(i, _)| i);
+        let rest = items.split_off(end);
+        Ok(Value::Array(if name == "take_while" { items } else { rest }))
+    }
+
+    fn call_reduce(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() < 2 || args.len() > 3 {
+            return Err(format!("reduce expects 2 or 3 arguments (array, function, initial), got {}", args.len()));
+        }
+
+        let mut args = args.into_iter();
+        let (array_val, func_val, initial) = (args.next().unwrap(), args.next().unwrap(), args.next());
+        if let Value::Array(arr) = array_val {
+            let mut items = arr.into_iter();
+            // Without an initial value the first element seeds the accumulator
+            let mut acc = match initial.or_else(|| items.next()) {
+                Some(val) => val,
+                None => return Err("reduce of an empty array with no initial value".to_string()),
+            };
+            for item in items {
+                acc = self.call_value("reduce callback", func_val.clone(), vec![acc, item])?;
+            }
+            Ok(acc)
+        } else {
+            Err(format!("reduce expects an array, got {}", array_val.type_name()))
+        }
+    }
+
+    fn call_sort(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(format!("sort expects 1 or 2 arguments (array, comparator), got {}", args.len()));
+        }
+
+        let mut args = args.into_iter();
+        let (array_val, comparator) = (args.next().unwrap(), args.next());
+        let mut items = match array_val {
+            Value::Array(arr) => arr,
+            _ => return Err(format!("sort expects an array, got {}", array_val.type_name())),
+        };
+
+        // sort_by can't fail, so remember the first error and report it afterwards
+        let mut error = None;
+        items.sort_by(|a, b| {
+            let ordering = match &comparator {
+                // The comparator returns a negative, zero, or positive number
+                Some(func) => self
+                    .call_value("sort comparator", func.clone(), vec![a.clone(), b.clone()])
+                    .and_then(|result| result.to_number())
+                    .map(|n| n.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+                None => self.cmp_value(a, b),
+            };
+            ordering.unwrap_or_else(|err| {
+                error.get_or_insert(err);
+                Ordering::Equal
+            })
+        });
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(Value::Array(items)),
+        }
+    }
+
+    /// Stable sort of objects by one property, compared like `<` compares values
+    fn call_sort_by_key(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!("sort_by_key expects 2 arguments (array, property name), got {}", args.len()));
+        }
+        let (items, key) = match (&args[0], &args[1]) {
+            (Value::Array(items), Value::String(key)) => (items, key),
+            (Value::Array(_), other) => {
+                return Err(format!("sort_by_key expects a String property name, got {}", other.type_name()))
+            }
+            (other, _) => return Err(format!("sort_by_key expects an array, got {}", other.type_name())),
+        };
+
+        let mut keyed = Vec::with_capacity(items.len());
+        for item in items {
+            let Value::Object { class_name, properties } = item else {
+                return Err(format!("sort_by_key expects an array of objects, got {}", item.type_name()));
+            };
+            keyed.push((self.read_property(class_name, properties, key)?, item.clone()));
+        }
+
+        // sort_by can't fail, so remember the first error and report it afterwards
+        let mut error = None;
+        keyed.sort_by(|(a, _), (b, _)| {
+            self.cmp_value(a, b).unwrap_or_else(|err| {
+                error.get_or_insert(err);
+                Ordering::Equal
+            })
+        });
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(Value::Array(keyed.into_iter().map(|(_, item)| item).collect())),
+        }
+    }
+
+    fn call_contains(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!("contains expects 2 arguments (array, value), got {}", args.len()));
+        }
+        match &args[0] {
+            Value::Array(arr) => {
+                for item in arr {
+                    if self.values_equal(item, &args[1])? {
+                        return Ok(Value::Boolean(true));
+                    }
+                }
+                Ok(Value::Boolean(false))
+            }
+            _ => Err(format!("contains expects an array, got {}", args[0].type_name())),
+        }
+    }
+
+    fn call_index_of(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!("index_of expects 2 arguments (array, value), got {}", args.len()));
+        }
+        match &args[0] {
+            Value::Array(arr) => {
+                for (i, item) in arr.iter().enumerate() {
+                    if self.values_equal(item, &args[1])? {
+                        return Ok(Value::Number(i as f64));
+                    }
+                }
+                Ok(Value::Number(-1.0))
+            }
+            _ => Err(format!("index_of expects an array, got {}", args[0].type_name())),
+        }
+    }
+
+    /// Sorted keys, values, or `[key, value]` entries of a map or an object's
+    /// properties. `own_keys` leaves out properties an object only has because a
+    /// parent class declares them, and private properties are only listed from
+    /// inside a class
+    fn call_keys(&self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(format!("{} expects 1 argument, got {}", name, args.len()));
+        }
+        let mut entries: Vec<(&String, &Value)> = match &args[0] {
+            Value::Map(map) if name != "own_keys" => map.iter().collect(),
+            Value::Object { class_name, properties } => {
+                let inherited = match self.get_variable(class_name) {
+                    Ok(Value::Class { parent: Some(parent), properties: declared, .. }) if name == "own_keys" => parent
+                        .default_properties()
+                        .into_keys()
+                        .filter(|key| !declared.contains_key(key))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                properties
+                    .iter()
+                    .filter(|(key, _)| !inherited.contains(key))
+                    .filter(|(key, _)| !key.starts_with('_') || self.in_context)
+                    .filter(|(key, _)| self.check_private(class_name, key, "list private property").is_ok())
+                    .collect()
+            }
+            other if name == "own_keys" => return Err(format!("own_keys expects an Object, got {}", other.type_name())),
+            other => return Err(format!("{} expects a Map or Object, got {}", name, other.type_name())),
+        };
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        let items = entries
+            .into_iter()
+            .map(|(key, value)| match name {
+                "values" => value.clone(),
+                "entries" => Value::Array(vec![Value::String(key.clone()), value.clone()]),
+                _ => Value::String(key.clone()),
+            })
+            .collect();
+        Ok(Value::Array(items))
+    }
+
+    /// Whether a map has a key or an object has a property. Private properties
+    /// count as missing outside their class, as they do for `keys`
+    fn call_has(&self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!("has expects 2 arguments (object, name), got {}", args.len()));
+        }
+        match (&args[0], &args[1]) {
+            (Value::Map(map), Value::String(key)) => Ok(Value::Boolean(map.contains_key(key))),
+            (Value::Object { class_name, properties }, Value::String(key)) => {
+                let visible = (!key.starts_with('_') || self.in_context)
+                    && self.check_private(class_name, key, "check private property").is_ok();
+                Ok(Value::Boolean(visible && properties.contains_key(key)))
+            }
+            (Value::Map(_) | Value::Object { .. }, other) => {
+                Err(format!("has expects a String name, got {}", other.type_name()))
+            }
+            (other, _) => Err(format!("has expects a Map or Object, got {}", other.type_name())),
+        }
+    }
+
+    /// `random()` is in [0, 1) and `random_int(lo, hi)` includes both bounds;
+    /// `seed(n)` restarts the sequence so that it repeats for the same `n`
+    fn call_random(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        match name {
+            "seed" => {
+                let n = args[0].to_number()?;
+                if n.fract() != 0.0 {
+                    return Err(format!("seed expects an integer, got {}", n));
+                }
+                self.rng = builtins::XorShift::new(n as i64 as u64);
+                Ok(Value::Null)
+            }
+            "random" => {
+                // The top 53 bits fill an f64 mantissa exactly
+                Ok(Value::Number((self.rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64))
+            }
+            _ => {
+                let lo = args[0].to_number()?;
+                let hi = args[1].to_number()?;
+                if lo.fract() != 0.0 || hi.fract() != 0.0 {
+                    return Err(format!("random_int expects integer bounds, got {} and {}", lo, hi));
+                }
+                if lo > hi {
+                    return Err(format!("random_int expects lo <= hi, got {} and {}", lo, hi));
+                }
+                // Beyond 2^53 not every integer is a Number, so the range can't be drawn from evenly
+                let limit = (1u64 << 53) as f64;
+                if lo.abs() > limit || hi.abs() > limit || hi - lo >= limit {
+                    return Err(format!("random_int expects bounds within 2^53 of zero and less than 2^53 apart, got {} and {}", lo, hi));
+                }
+                let span = (hi - lo) as u64 + 1;
+                Ok(Value::Number(lo + self.rng.below(span) as f64))
+            }
+        }
+    }
+
+    /// The value of a thunk, computing it on the first call only
+    fn call_force(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(format!("force expects 1 argument, got {}", args.len()));
+        }
+        let Value::Thunk { func, memo } = &args[0] else {
+            return Err(format!("force expects a Thunk, got {}", args[0].type_name()));
+        };
+        if let Some(value) = &*memo.borrow() {
+            return Ok(value.clone());
+        }
+        let value = self.call_value("lazy function", (**func).clone(), Vec::new())?;
+        *memo.borrow_mut() = Some(value.clone());
+        Ok(value)
+    }
+
+    /// Sorted names of the methods an object's class defines or inherits; private
+    /// methods are only listed from inside the class
+    fn call_methods(&self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(format!("methods expects 1 argument, got {}", args.len()));
+        }
+        let Value::Object { class_name, .. } = &args[0] else {
+            return Err(format!("methods expects an Object, got {}", args[0].type_name()));
+        };
+        let class = self.get_variable(class_name)?;
+        let names = class
+            .method_names()
+            .into_iter()
+            .filter(|name| self.check_private(class_name, name, "list private method").is_ok())
+            .map(Value::String)
+            .collect();
+        Ok(Value::Array(names))
+    }
+
+    fn call_to_json(&mut self, args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(format!("to_json expects 1 argument, got {}", args.len()));
+        }
+        Ok(Value::String(self.serialize_json(&args[0])?))
+    }
+
+    /// Serialize a value as JSON. Map and object keys are sorted; private
+    /// properties are left out, and an object whose class defines `toJSON()`
+    /// is serialized as that method's return value instead
+    fn serialize_json(&mut self, value: &Value) -> Result<String, String> {
+        match value {
+            Value::Null => Ok("null".to_string()),
+            Value::Boolean(b) => Ok(b.to_string()),
+            Value::Number(n) if !n.is_finite() => Ok("null".to_string()),
+            Value::Number(_) => Ok(value.to_string()),
+            Value::String(s) => Ok(json_quote(s)),
+            Value::Array(arr) => {
+                let mut items = Vec::new();
+                for item in arr {
+                    items.push(self.serialize_json(item)?);
+                }
+                Ok(format!("[{}]", items.join(",")))
+            }
+            Value::Map(map) => self.json_object(map.iter()),
+            Value::Object { class_name, properties } => {
+                let has_to_json = matches!(
+                    self.get_variable(class_name),
+                    Ok(class) if class.find_method("toJSON").is_some()
+                );
+                if has_to_json {
+                    let (result, _) = self.call_method(value.clone(), "toJSON", Vec::new())?;
+                    return self.serialize_json(&result);
+                }
+                // Private properties are left out, as they are for `keys`
+                let visible: Vec<_> = properties
+                    .iter()
+                    .filter(|(name, _)| !name.starts_with('_'))
+                    .filter(|(name, _)| self.check_private(class_name, name, "serialize private property").is_ok())
+                    .collect();
+                self.json_object(visible.into_iter())
+            }
+            _ => Err(format!("to_json cannot serialize {}", value.type_name())),
+        }
+    }
+
+    fn json_object<'a>(&mut self, entries: impl Iterator<Item = (&'a String, &'a Value)>) -> Result<String, String> {
+        let mut entries: Vec<_> = entries.collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        let mut fields = Vec::new();
+        for (key, val) in entries {
+            fields.push(format!("{}:{}", json_quote(key), self.serialize_json(val)?));
+        }
+        Ok(format!("{{{}}}", fields.join(",")))
+    }
+
+    /// One line from the input source without its line ending, or null at end of input
+    fn read_input_line(&mut self) -> Result<Value, String> {
+        let mut line = String::new();
+        let read = match &mut self.input {
+            Some(reader) => reader.read_line(&mut line),
+            None => io::stdin().lock().read_line(&mut line),
+        }
+        .map_err(|e| format!("Cannot read input: {}", e))?;
+        if read == 0 {
+            return Ok(Value::Null);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Value::String(line))
+    }
+
     fn match_value(&mut self, value: &Value, cases: &[MatchCase]) -> Result<Value, String> {
         for case in cases {
-            if self.pattern_matches(&case.pattern, value)? {
-                return self.evaluate_expr(&case.body);
+            if !self.pattern_matches(&case.pattern, value)? {
+                continue;
+            }
+
+            // Bindings are visible to both the guard and the body
+            self.push_scope();
+            if let Pattern::Identifier(name) = &case.pattern {
+                if !value::TYPE_NAMES.contains(&name.as_str()) {
+                    self.define_variable(name.clone(), value.clone());
+                }
+            }
+            let result = self.evaluate_case(case);
+            self.pop_scope();
+
+            if let Some(val) = result? {
+                return Ok(val);
             }
         }
         Err("No matching case found".to_string())
     }
 
-    fn pattern_matches(&self, pattern: &Pattern, value: &Value) -> Result<bool, String> {
+    fn evaluate_case(&mut self, case: &MatchCase) -> Result<Option<Value>, String> {
+        if let Some(guard) = &case.guard {
+            if !self.evaluate_expr(guard)?.is_truthy() {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.evaluate_expr(&case.body)?))
+    }
+
+    fn pattern_matches(&mut self, pattern: &Pattern, value: &Value) -> Result<bool, String> {
         match pattern {
             Pattern::Wildcard => Ok(true),
             Pattern::Literal(lit) => {
                 let lit_val = self.literal_to_value(lit);
-                Ok(self.values_equal(&lit_val, value))
+                self.values_equal(&lit_val, value)
             }
             Pattern::Identifier(id) => {
-                // Match against type name
-                Ok(id == value.type_name())
+                // Type names match by type; any other identifier binds the value
+                if value::TYPE_NAMES.contains(&id.as_str()) {
+                    Ok(id == value.type_name())
+                } else {
+                    Ok(true)
+                }
+            }
+            Pattern::Or(alternatives) => {
+                for alternative in alternatives {
+                    if self.pattern_matches(alternative, value)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
             }
         }
     }
 
+    /// A function closing over the variables currently in scope
+    fn function_value(&self, params: &[Param], body: &[Stmt]) -> Value {
+        Value::Function {
+            params: params.to_vec(),
+            body: body.to_vec(),
+            closure: self.capture_closure(),
+        }
+    }
+
     fn capture_closure(&self) -> HashMap<String, Value> {
         let mut closure = HashMap::new();
         
@@ -640,3 +2079,1803 @@ impl Interpreter {
         closure
     }
 }
+
+/// Quote and escape a string for JSON output
+fn json_quote(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(source: &str) -> Program {
+        let mut lexer = Lexer::new(source.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse().unwrap()
+    }
+
+    fn run(source: &str) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        interpreter.execute(&parse(source)).unwrap();
+        interpreter
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Value {
+        interpreter.get_variable(name).unwrap()
+    }
+
+    fn run_err(source: &str) -> String {
+        let mut interpreter = Interpreter::new();
+        interpreter.execute(&parse(source)).unwrap_err().message
+    }
+
+    #[test]
+    fn test_with_overrides_copy() {
+        let interpreter = run(
+            "class Point { x = 1; y = 2; }
+             p = new Point()
+             q = with(p, { x: 10 })
+             px = p.x
+             qx = q.x
+             qy = q.y",
+        );
+        assert_eq!(global(&interpreter, "px"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "qx"), Value::Number(10.0));
+        assert_eq!(global(&interpreter, "qy"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_with_and_merge_respect_private_and_const() {
+        assert_eq!(
+            run_err(&format!("{}\nb = with(account, {{ id: 9 }})", ACCOUNT)),
+            "Cannot assign to const property 'id' of class 'Account'"
+        );
+        assert_eq!(
+            run_err(&format!("{}\nb = with(account, {{ balance: 7 }})", ACCOUNT)),
+            "Cannot assign private property 'balance' of class 'Account' from outside the class"
+        );
+        assert_eq!(
+            run_err(&format!("{}\nb = merge(account, new Account(9, 15))", ACCOUNT)),
+            "Cannot assign to const property 'id' of class 'Account'"
+        );
+        assert_eq!(
+            run_err(&format!("{}\nb = merge(account, new Account(7, 500))", ACCOUNT)),
+            "Cannot assign private property 'balance' of class 'Account' from outside the class"
+        );
+        // Properties that don't change can be merged
+        let interpreter = run(&format!("{}\nsame = merge(account, account).total()", ACCOUNT));
+        assert_eq!(global(&interpreter, "same"), Value::Number(15.0));
+    }
+
+    #[test]
+    fn test_match_guard_falls_through() {
+        let interpreter = run(
+            "func classify(n) {
+                 return match (n) {
+                     case x if x > 10 => \"big\"
+                     case 5 => \"five\"
+                     case _ => \"small\"
+                 }
+             }
+             a = classify(20)
+             b = classify(5)
+             c = classify(3)",
+        );
+        assert_eq!(global(&interpreter, "a"), Value::String("big".to_string()));
+        assert_eq!(global(&interpreter, "b"), Value::String("five".to_string()));
+        assert_eq!(global(&interpreter, "c"), Value::String("small".to_string()));
+    }
+
+    #[test]
+    fn test_match_parenthesized_guard() {
+        let interpreter = run(
+            "ready = true
+             a = match (1) {
+                 case n if (ready) => \"yes\"
+                 case _ => \"no\"
+             }
+             b = match (2) {
+                 case n if (n < 0) => \"negative\"
+                 case n if (n) => n
+                 case _ => \"zero\"
+             }",
+        );
+        assert_eq!(global(&interpreter, "a"), Value::String("yes".to_string()));
+        assert_eq!(global(&interpreter, "b"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_match_guard_sees_outer_variables() {
+        let interpreter = run(
+            "limit = 100
+             func classify(n, threshold) {
+                 return match (n) {
+                     case x if x > limit => \"huge\"
+                     case x if x > threshold => \"big\"
+                     case _ => \"small\"
+                 }
+             }
+             a = classify(500, 10)
+             b = classify(20, 10)
+             c = classify(20, 50)
+             x = \"outer\"
+             d = match (3) { case x if x < 5 => x }",
+        );
+        assert_eq!(global(&interpreter, "a"), Value::String("huge".to_string()));
+        assert_eq!(global(&interpreter, "b"), Value::String("big".to_string()));
+        assert_eq!(global(&interpreter, "c"), Value::String("small".to_string()));
+        assert_eq!(global(&interpreter, "d"), Value::Number(3.0));
+        // The pattern binding shadows `x` only inside the case
+        assert_eq!(global(&interpreter, "x"), Value::String("outer".to_string()));
+    }
+
+    #[test]
+    fn test_match_type_pattern_with_guard() {
+        let interpreter = run(
+            "r = match (\"hi\") {
+                 case Number => \"number\"
+                 case String if len(\"hi\") > 5 => \"long string\"
+                 case String => \"string\"
+             }",
+        );
+        assert_eq!(global(&interpreter, "r"), Value::String("string".to_string()));
+    }
+
+    #[test]
+    fn test_structural_array_equality() {
+        let interpreter = run(
+            "same = [1, 2] == [1, 2]
+             different = [1, 2] == [1, 3]
+             shorter = [1, 2] == [1]
+             nested = [[1, \"a\"], [true]] == [[1, \"a\"], [true]]
+             nested_different = [[1], [2]] != [[1], [3]]",
+        );
+        assert_eq!(global(&interpreter, "same"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "different"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "shorter"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "nested"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "nested_different"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_structural_map_and_object_equality() {
+        let interpreter = run(
+            "class Point { x = 1; y = 2; }
+             objects = new Point() == new Point()
+             maps = { a: [1], b: 2 } == { b: 2, a: [1] }
+             maps_different = { a: 1 } == { a: 1, b: 2 }",
+        );
+        assert_eq!(global(&interpreter, "objects"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "maps"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "maps_different"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_string_and_boolean_ordering() {
+        let interpreter = run(
+            "a = \"apple\" < \"banana\"
+             b = \"banana\" <= \"apple\"
+             c = \"b\" >= \"b\"
+             d = false < true
+             e = 2 > 10",
+        );
+        assert_eq!(global(&interpreter, "a"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "b"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "c"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "d"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "e"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_compare_string_to_number_errors() {
+        let err = run_err("x = \"apple\" < 5");
+        assert!(err.contains("Cannot convert 'apple' to number"), "{}", err);
+    }
+
+    #[test]
+    fn test_debug_hook_sees_each_statement() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let visited = Rc::new(RefCell::new(Vec::new()));
+        let recorder = Rc::clone(&visited);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_debug_hook(Box::new(move |stmt: &Stmt| {
+            recorder.borrow_mut().push((stmt.kind.name(), stmt.line));
+        }));
+
+        let program = parse("x = 1\nif (x > 0) {\n    y = 2\n}\nz = 3");
+        interpreter.execute(&program).unwrap();
+        assert_eq!(
+            *visited.borrow(),
+            vec![("VarDecl", 1), ("If", 2), ("Block", 2), ("VarDecl", 3), ("VarDecl", 5)]
+        );
+    }
+
+    #[test]
+    fn test_override_of_parent_method() {
+        let interpreter = run(
+            "class Animal {
+                 func speak() { return \"...\" }
+             }
+             class Dog extends Animal {
+                 override func speak() { return \"Woof\" }
+             }
+             class Puppy extends Dog {
+                 override func speak() { return \"Yip\" }
+             }
+             sound = new Puppy().speak()",
+        );
+        assert_eq!(global(&interpreter, "sound"), Value::String("Yip".to_string()));
+    }
+
+    #[test]
+    fn test_override_without_parent_method_errors() {
+        let err = run_err(
+            "class Animal {
+                 func speak() { return \"...\" }
+             }
+             class Dog extends Animal {
+                 override func fetch() { return \"ball\" }
+             }",
+        );
+        assert_eq!(err, "Method 'fetch' in class 'Dog' is marked override but no parent class defines it");
+
+        let err = run_err("class Rock { override func speak() { return 1 } }");
+        assert!(err.contains("marked override"), "{}", err);
+    }
+
+    #[test]
+    fn test_array_methods_match_free_functions() {
+        let interpreter = run(
+            "arr = [3, 1, 2]
+             map_m = arr.map((x) => x * 2)
+             map_f = map(arr, (x) => x * 2)
+             filter_m = arr.filter((x) => x > 1)
+             filter_f = filter(arr, (x) => x > 1)
+             reduce_m = arr.reduce((acc, x) => acc + x, 10)
+             reduce_f = reduce(arr, (acc, x) => acc + x, 10)
+             sort_m = arr.sort()
+             sort_f = sort(arr)
+             push_m = arr.push(4)
+             push_f = push(arr, 4)
+             pop_m = arr.pop()
+             pop_f = pop(arr)
+             join_m = arr.join(\"-\")
+             join_f = join(arr, \"-\")
+             contains_m = arr.contains(2)
+             contains_f = contains(arr, 2)
+             index_of_m = arr.index_of(2)
+             index_of_f = index_of(arr, 2)
+             reverse_m = arr.reverse()
+             reverse_f = reverse(arr)
+             slice_m = arr.slice(1)
+             slice_f = slice(arr, 1)",
+        );
+
+        let array = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        let expected = [
+            ("map", array(&[6.0, 2.0, 4.0])),
+            ("filter", array(&[3.0, 2.0])),
+            ("reduce", Value::Number(16.0)),
+            ("sort", array(&[1.0, 2.0, 3.0])),
+            ("push", array(&[3.0, 1.0, 2.0, 4.0])),
+            ("pop", array(&[3.0, 1.0])),
+            ("join", Value::String("3-1-2".to_string())),
+            ("contains", Value::Boolean(true)),
+            ("index_of", Value::Number(2.0)),
+            ("reverse", array(&[2.0, 1.0, 3.0])),
+            ("slice", array(&[1.0, 2.0])),
+        ];
+        for (method, value) in expected {
+            let method_form = global(&interpreter, &format!("{}_m", method));
+            let function_form = global(&interpreter, &format!("{}_f", method));
+            assert_eq!(method_form, function_form, "{} forms differ", method);
+            assert_eq!(method_form, value, "{} result", method);
+        }
+        // The receiver is left unchanged
+        assert_eq!(global(&interpreter, "arr"), array(&[3.0, 1.0, 2.0]));
+    }
+
+    #[test]
+    fn test_sort_with_comparator_and_errors() {
+        let interpreter = run(
+            "desc = sort([1, 3, 2], (a, b) => b - a)
+             words = [\"pear\", \"apple\"].sort()
+             missing = index_of([1, 2], 5)",
+        );
+        let numbers = [3.0, 2.0, 1.0].iter().map(|n| Value::Number(*n)).collect();
+        assert_eq!(global(&interpreter, "desc"), Value::Array(numbers));
+        assert_eq!(
+            global(&interpreter, "words"),
+            Value::Array(vec![Value::String("apple".to_string()), Value::String("pear".to_string())])
+        );
+        assert_eq!(global(&interpreter, "missing"), Value::Number(-1.0));
+
+        assert!(run_err("x = sort([1, \"a\"])").contains("Cannot convert 'a' to number"));
+        assert_eq!(run_err("x = [1].shuffle()"), "Method 'shuffle' not found on Array");
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let interpreter = run(
+            r#"a = 1
+               b = 2
+               sum = "sum=${a+b}"
+               nested = "outer ${"inner ${a * 10}"}!"
+               calls = "len ${len([1, 2, 3])} and ${ {k: a} }"
+               escaped = "\${a}""#,
+        );
+        assert_eq!(global(&interpreter, "sum"), Value::String("sum=3".to_string()));
+        assert_eq!(global(&interpreter, "nested"), Value::String("outer inner 10!".to_string()));
+        assert_eq!(global(&interpreter, "calls"), Value::String("len 3 and {k: 1}".to_string()));
+        assert_eq!(global(&interpreter, "escaped"), Value::String("${a}".to_string()));
+    }
+
+    #[test]
+    fn test_strict_mode_refuses_coercion() {
+        let source = r#"result = "5" - 3"#;
+        assert_eq!(global(&run(source), "result"), Value::Number(2.0));
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_strict(true);
+        for source in [r#""5" - 3"#, r#"true * 2"#, r#"-"1""#] {
+            let err = interpreter.execute(&parse(source)).unwrap_err().message;
+            assert!(err.contains("strict mode"), "{}: {}", source, err);
+        }
+        interpreter.execute(&parse(r#"ok = 6 / 3 == 2 && "a" < "b""#)).unwrap();
+        assert_eq!(interpreter.globals.get("ok"), Some(&Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let interpreter = run(
+            r#"class Point {
+                   x = 1
+                   y = 2
+                   _secret = 3
+                   private hidden = 4
+               }
+               class Money {
+                   cents = 1250
+                   func toJSON() {
+                       return { amount: cents / 100, currency: "EUR" }
+                   }
+               }
+               plain = to_json(new Point())
+               custom = to_json([new Money(), null, true, "say \"hi\"\n"])"#,
+        );
+        assert_eq!(global(&interpreter, "plain"), Value::String(r#"{"x":1,"y":2}"#.to_string()));
+        assert_eq!(
+            global(&interpreter, "custom"),
+            Value::String(r#"[{"amount":12.5,"currency":"EUR"},null,true,"say \"hi\"\n"]"#.to_string())
+        );
+        assert!(run_err("to_json(print)").contains("cannot serialize Function"));
+        let interpreter = run(&format!("{}\njson = to_json(account)", ACCOUNT));
+        assert_eq!(global(&interpreter, "json"), Value::String(r#"{"id":7}"#.to_string()));
+    }
+
+    #[test]
+    fn test_loop_closures_capture_iteration_values() {
+        let interpreter = run(
+            r#"counters = []
+               for (i = 0; i < 3; i = i + 1) {
+                   counters = push(counters, (x) => i)
+               }
+               from_for = map(counters, (f) => f(0))
+
+               counters = []
+               for (item in [10, 20, 30]) {
+                   counters = push(counters, (x) => item + x)
+               }
+               from_foreach = map(counters, (f) => f(1))"#,
+        );
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        assert_eq!(global(&interpreter, "from_for"), numbers(&[0.0, 1.0, 2.0]));
+        assert_eq!(global(&interpreter, "from_foreach"), numbers(&[11.0, 21.0, 31.0]));
+
+        // The loop variable is still there once the loop ends, whether or not it existed before
+        let interpreter = run(
+            "for (i = 0; i < 3; i = i + 1) { }
+             last = i
+             for (i = 5; i < 7; i = i + 1) { }
+             func f() {
+                 for (j = 0; j < 2; j = j + 1) { }
+                 return j
+             }
+             inner = f()",
+        );
+        assert_eq!(global(&interpreter, "last"), Value::Number(3.0));
+        assert_eq!(global(&interpreter, "i"), Value::Number(7.0));
+        assert_eq!(global(&interpreter, "inner"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_this_inside_methods() {
+        let interpreter = run(
+            r#"class Counter {
+                   count = 0
+                   step = 2
+                   func helper() {
+                       return step * 10
+                   }
+                   func describe() {
+                       return this.helper() + this.count
+                   }
+                   func bump() {
+                       this.count = this.count + step
+                       count = count + 1
+                       return this.count
+                   }
+                   func bumpTwice() {
+                       this.bump()
+                       this.bump()
+                       return count
+                   }
+               }
+               c = new Counter()
+               bumped = c.bump()
+               twice = c.bumpTwice()
+               described = c.describe()
+               final_count = c.count"#,
+        );
+        assert_eq!(global(&interpreter, "bumped"), Value::Number(3.0));
+        assert_eq!(global(&interpreter, "twice"), Value::Number(9.0));
+        assert_eq!(global(&interpreter, "described"), Value::Number(29.0));
+        assert_eq!(global(&interpreter, "final_count"), Value::Number(9.0));
+    }
+
+    #[test]
+    fn test_inherited_methods() {
+        let interpreter = run(
+            r#"class Animal {
+                   name = "animal"
+                   func speak() {
+                       return name + " makes a sound"
+                   }
+                   func kind() {
+                       return "animal"
+                   }
+               }
+               class Dog extends Animal {
+                   name = "Rex"
+                   override func kind() {
+                       return "dog"
+                   }
+               }
+               class Puppy extends Dog {
+               }
+               d = new Dog()
+               spoken = d.speak()
+               kind = d.kind()
+               puppy_kind = new Puppy().kind()"#,
+        );
+        assert_eq!(global(&interpreter, "spoken"), Value::String("Rex makes a sound".to_string()));
+        assert_eq!(global(&interpreter, "kind"), Value::String("dog".to_string()));
+        assert_eq!(global(&interpreter, "puppy_kind"), Value::String("dog".to_string()));
+    }
+
+    #[test]
+    fn test_repeat_loop() {
+        let interpreter = run(
+            r#"total = 0
+               repeat (4) {
+                   total = total + 3
+               }
+               repeat (0) {
+                   total = 100
+               }
+               func first_big(limit) {
+                   n = 1
+                   repeat (10) {
+                       n = n * 2
+                       if (n > limit) {
+                           return n
+                       }
+                   }
+                   return -1
+               }
+               big = first_big(20)"#,
+        );
+        assert_eq!(global(&interpreter, "total"), Value::Number(12.0));
+        assert_eq!(global(&interpreter, "big"), Value::Number(32.0));
+
+        assert_eq!(run_err("repeat (-1) { }"), "repeat expects a non-negative integer count, got -1");
+        assert_eq!(run_err("repeat (1.5) { }"), "repeat expects a non-negative integer count, got 1.5");
+        assert_eq!(run_err(r#"repeat ("3") { }"#), "repeat expects a number, got String");
+    }
+
+    #[test]
+    fn test_equality_never_coerces() {
+        let interpreter = run(
+            r#"number_string = 5 == "5"
+               bool_number = true == 1
+               null_zero = null != 0
+               loose = loose_eq(5, "5")"#,
+        );
+        assert_eq!(global(&interpreter, "number_string"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "bool_number"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "null_zero"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "loose"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_init_constructor() {
+        let interpreter = run(
+            r#"class Person {
+                   name = "nobody"
+                   greeting = ""
+                   func init(name, title) {
+                       this.name = name
+                       greeting = "Hello, " + title + " " + name
+                   }
+               }
+               class Student extends Person {
+               }
+               class Plain {
+                   x = 1
+               }
+               p = new Person("Ada", "Dr.")
+               name = p.name
+               greeting = p.greeting
+               inherited = new Student("Bob", "Mr.").name
+               plain = new Plain(42).x"#,
+        );
+        assert_eq!(global(&interpreter, "name"), Value::String("Ada".to_string()));
+        assert_eq!(global(&interpreter, "greeting"), Value::String("Hello, Dr. Ada".to_string()));
+        assert_eq!(global(&interpreter, "inherited"), Value::String("Bob".to_string()));
+        assert_eq!(global(&interpreter, "plain"), Value::Number(1.0));
+    }
+
+    #[test]
+    fn test_trailing_lambda() {
+        let interpreter = run(
+            r#"doubled = map([1, 2, 3]) { x => x * 2 }
+               descending = [1, 3, 2].sort() { a, b => b - a }
+               func apply_twice(value, f) {
+                   return f(f(value))
+               }
+               twice = apply_twice(3) { n => n * n }
+               checked = false
+               if (len(doubled) == 3) {
+                   checked = true
+               }"#,
+        );
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        assert_eq!(global(&interpreter, "doubled"), numbers(&[2.0, 4.0, 6.0]));
+        assert_eq!(global(&interpreter, "descending"), numbers(&[3.0, 2.0, 1.0]));
+        assert_eq!(global(&interpreter, "twice"), Value::Number(81.0));
+        assert_eq!(global(&interpreter, "checked"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_super_calls() {
+        let interpreter = run(
+            r#"class Shape {
+                   name = "shape"
+                   sides = 0
+                   func init(sides) {
+                       this.sides = sides
+                   }
+                   func describe() {
+                       return name + " with " + sides + " sides"
+                   }
+               }
+               class Square extends Shape {
+                   size = 0
+                   func init(size) {
+                       super("four")
+                       this.size = size
+                       name = "square"
+                   }
+                   override func describe() {
+                       return super.describe() + " of size " + size
+                   }
+               }
+               class Cube extends Square {
+                   override func describe() {
+                       return "cube: " + super.describe()
+                   }
+               }
+               sq = new Square("2")
+               sides = sq.sides
+               described = sq.describe()
+               cube = new Cube("3").describe()"#,
+        );
+        assert_eq!(global(&interpreter, "sides"), Value::String("four".to_string()));
+        assert_eq!(global(&interpreter, "described"), Value::String("square with four sides of size 2".to_string()));
+        assert_eq!(global(&interpreter, "cube"), Value::String("cube: square with four sides of size 3".to_string()));
+        assert_eq!(run_err("super.describe()"), "'super' can only be used inside a method");
+    }
+
+    #[test]
+    fn test_find_and_find_index() {
+        let interpreter = run(
+            r#"calls = 0
+               func is_big(x) {
+                   calls = calls + 1
+                   return x > 10
+               }
+               items = [5, 12, 30, 7]
+               found = find(items, is_big)
+               calls_after_find = calls
+               index = items.find_index(is_big)
+               missing = items.find((x) => x > 100)
+               missing_index = find_index(items, (x) => x > 100)"#,
+        );
+        assert_eq!(global(&interpreter, "found"), Value::Number(12.0));
+        assert_eq!(global(&interpreter, "calls_after_find"), Value::Number(2.0));
+        assert_eq!(global(&interpreter, "index"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "calls"), Value::Number(4.0));
+        assert_eq!(global(&interpreter, "missing"), Value::Null);
+        assert_eq!(global(&interpreter, "missing_index"), Value::Number(-1.0));
+    }
+
+    #[test]
+    fn test_to_string_method() {
+        let interpreter = run(
+            r#"class Point {
+                   x = 1
+                   y = 2
+                   func toString() {
+                       return "(" + to_string(x) + ", " + to_string(y) + ")"
+                   }
+               }
+               class Plain {
+               }
+               p = new Point()
+               shown = to_string(p)
+               interpolated = "p is ${p}"
+               plain = to_string(new Plain())"#,
+        );
+        assert_eq!(global(&interpreter, "shown"), Value::String("(1, 2)".to_string()));
+        assert_eq!(global(&interpreter, "interpolated"), Value::String("p is (1, 2)".to_string()));
+        assert_eq!(global(&interpreter, "plain"), Value::String("<Plain object>".to_string()));
+
+        let err = run_err("class Bad {\n func toString() { return 1 }\n}\nprint(new Bad())");
+        assert_eq!(err, "toString on 'Bad' must return a String, got Number");
+    }
+
+    #[test]
+    fn test_or_patterns() {
+        let interpreter = run(
+            r#"func size(n) {
+                   return match (n) {
+                       case 1, 2, 3 => "small"
+                       case 4, 5 if n == 5 => "five"
+                       case "many", Boolean => "other"
+                       case _ => "big"
+                   }
+               }
+               sizes = map([2, 3, 5, 4, "many", true], size)
+               x = 9
+               unbound = match (7) {
+                   case 1, x => x
+               }"#,
+        );
+        let strings = |items: &[&str]| Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect());
+        assert_eq!(global(&interpreter, "sizes"), strings(&["small", "small", "five", "big", "other", "other"]));
+        // Identifiers in an or-pattern match anything but don't bind the value
+        assert_eq!(global(&interpreter, "unbound"), Value::Number(9.0));
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        let interpreter = run(
+            r#"and = 6 & 3
+               or = 6 | 3
+               xor = 6 ^ 3
+               shifted = 1 << 4
+               halved = -16 >> 2
+               not = ~0
+               precedence = 1 | 2 ^ 3 & 1 << 1"#,
+        );
+        assert_eq!(global(&interpreter, "and"), Value::Number(2.0));
+        assert_eq!(global(&interpreter, "or"), Value::Number(7.0));
+        assert_eq!(global(&interpreter, "xor"), Value::Number(5.0));
+        assert_eq!(global(&interpreter, "shifted"), Value::Number(16.0));
+        assert_eq!(global(&interpreter, "halved"), Value::Number(-4.0));
+        assert_eq!(global(&interpreter, "not"), Value::Number(-1.0));
+        // 1 | (2 ^ (3 & (1 << 1)))
+        assert_eq!(global(&interpreter, "precedence"), Value::Number(1.0));
+        assert_eq!(run_err("x = 1.5 & 1"), "'&' expects integer operands, got 1.5");
+        assert_eq!(run_err("x = 1 << 64"), "'<<' expects a shift between 0 and 63, got 64");
+    }
+
+    #[test]
+    fn test_power_operator() {
+        let interpreter = run(
+            "negated = -2 ** 2
+             reciprocal = 2 ** -1
+             tower = 2 ** 3 ** 2
+             mixed = 3 * 2 ** 2 + 1
+             grouped = (-2) ** 2",
+        );
+        assert_eq!(global(&interpreter, "negated"), Value::Number(-4.0));
+        assert_eq!(global(&interpreter, "reciprocal"), Value::Number(0.5));
+        assert_eq!(global(&interpreter, "tower"), Value::Number(512.0));
+        assert_eq!(global(&interpreter, "mixed"), Value::Number(13.0));
+        assert_eq!(global(&interpreter, "grouped"), Value::Number(4.0));
+    }
+
+    #[test]
+    fn test_in_operator() {
+        let interpreter = run(
+            r#"class Point { x = 1; _secret = 2; }
+               p = new Point()
+               arrays = [2 in [1, 2, 3], 4 in [1, 2, 3], [1] in [[1], [2]]]
+               strings = ["ell" in "hello", "xyz" in "hello", "" in "hello"]
+               maps = ["a" in { a: 1 }, "b" in { a: 1 }, "x" in p, "_secret" in p]
+               looped = []
+               for (n in [1, 2]) {
+                   looped = push(looped, n in [2])
+               }"#,
+        );
+        let bools = |items: &[bool]| Value::Array(items.iter().map(|b| Value::Boolean(*b)).collect());
+        assert_eq!(global(&interpreter, "arrays"), bools(&[true, false, true]));
+        assert_eq!(global(&interpreter, "strings"), bools(&[true, false, true]));
+        assert_eq!(global(&interpreter, "maps"), bools(&[true, false, true, false]));
+        assert_eq!(global(&interpreter, "looped"), bools(&[false, true]));
+        assert_eq!(run_err("x = 1 in \"123\""), "Cannot check whether Number is in String");
+    }
+
+    #[test]
+    fn test_match_bodyless_cases_fall_through() {
+        let interpreter = run(
+            r#"func kind(n) {
+                   return match (n) {
+                       case 1
+                       case 2 if n > 1
+                       case 3 => "small ${n}"
+                       case _ => "big"
+                   }
+               }
+               kinds = map([1, 2, 3, 4], kind)"#,
+        );
+        let strings = |items: &[&str]| Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect());
+        assert_eq!(global(&interpreter, "kinds"), strings(&["small 1", "small 2", "small 3", "big"]));
+    }
+
+    #[test]
+    fn test_is_operator() {
+        let interpreter = run(
+            r#"class Animal {
+               }
+               class Dog extends Animal {
+               }
+               class Car {
+               }
+               d = new Dog()
+               checks = [5 is Number, "5" is Number, d is Dog, d is Animal, d is Object, d is Car, new Animal() is Dog]
+               combined = 1 + 1 is Number && d is Animal"#,
+        );
+        let bools = |items: &[bool]| Value::Array(items.iter().map(|b| Value::Boolean(*b)).collect());
+        assert_eq!(
+            global(&interpreter, "checks"),
+            bools(&[true, false, true, true, true, false, false])
+        );
+        assert_eq!(global(&interpreter, "combined"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_keys_and_own_keys() {
+        let interpreter = run(
+            r#"class Animal {
+                   name = "animal"
+                   legs = 4
+                   _id = 0
+               }
+               class Dog extends Animal {
+                   legs = 4
+                   breed = "mutt"
+                   func init() {
+                       this.tricks = 0
+                   }
+               }
+               d = new Dog()
+               all = keys(d)
+               own = own_keys(d)
+               map_keys = keys({b: 1, a: 2})"#,
+        );
+        let strings = |items: &[&str]| Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect());
+        assert_eq!(global(&interpreter, "all"), strings(&["breed", "legs", "name", "tricks"]));
+        assert_eq!(global(&interpreter, "own"), strings(&["breed", "legs", "tricks"]));
+        assert_eq!(global(&interpreter, "map_keys"), strings(&["a", "b"]));
+    }
+
+    #[test]
+    fn test_has_property_or_key() {
+        let interpreter = run(
+            r#"class User {
+                   name = "ann"
+                   email = null
+                   _token = "secret"
+               }
+               user = new User()
+               has_name = has(user, "name")
+               has_email = has(user, "email")
+               has_age = has(user, "age")
+               has_token = has(user, "_token")
+               has_key = has({a: 1}, "a")
+               missing_key = has({a: 1}, "b")"#,
+        );
+        assert_eq!(global(&interpreter, "has_name"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "has_email"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "has_age"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "has_token"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "has_key"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "missing_key"), Value::Boolean(false));
+        assert_eq!(run_err("has([1], \"a\")"), "has expects a Map or Object, got Array");
+    }
+
+    #[test]
+    fn test_values_and_entries() {
+        let interpreter = run(
+            r#"class Book {
+                   title = "Dune"
+                   year = 1965
+                   author = "Herbert"
+                   private isbn = "0441013597"
+               }
+               book = new Book()
+               names = keys(book)
+               vals = values(book)
+               pairs = entries(book)
+               map_values = values({b: 1, a: 2})"#,
+        );
+        let string = |s: &str| Value::String(s.to_string());
+        assert_eq!(global(&interpreter, "names"), Value::Array(vec![string("author"), string("title"), string("year")]));
+        assert_eq!(
+            global(&interpreter, "vals"),
+            Value::Array(vec![string("Herbert"), string("Dune"), Value::Number(1965.0)])
+        );
+        assert_eq!(
+            global(&interpreter, "pairs"),
+            Value::Array(vec![
+                Value::Array(vec![string("author"), string("Herbert")]),
+                Value::Array(vec![string("title"), string("Dune")]),
+                Value::Array(vec![string("year"), Value::Number(1965.0)]),
+            ])
+        );
+        assert_eq!(global(&interpreter, "map_values"), Value::Array(vec![Value::Number(2.0), Value::Number(1.0)]));
+    }
+
+    #[test]
+    fn test_default_parameters() {
+        let interpreter = run(
+            r#"func greet(name, greeting = "Hi", punctuation = greeting + "!") {
+                   return greeting + " " + name + punctuation
+               }
+               one = greet("Ann")
+               two = greet("Bob", "Hello")
+               three = greet("Cy", "Yo", "?")
+               class Counter {
+                   count = 0
+                   func add(amount = 1) {
+                       count = count + amount
+                       return count
+                   }
+               }
+               c = new Counter()
+               c.add()
+               total = c.add(5)"#,
+        );
+        assert_eq!(global(&interpreter, "one"), Value::String("Hi AnnHi!".to_string()));
+        assert_eq!(global(&interpreter, "two"), Value::String("Hello BobHello!".to_string()));
+        assert_eq!(global(&interpreter, "three"), Value::String("Yo Cy?".to_string()));
+        assert_eq!(global(&interpreter, "total"), Value::Number(6.0));
+
+        let err = run_err("func f(a, b = 1) { return a }\nf()");
+        assert_eq!(err, "Function f expects 1 to 2 arguments, got 0");
+    }
+
+    #[test]
+    fn test_default_parameter_uses_earlier_argument() {
+        let interpreter = run(
+            r#"func scale(a, b = a * 2, c = a + b) {
+                   return [a, b, c]
+               }
+               defaults = scale(3)
+               given = scale(3, 1)"#,
+        );
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        assert_eq!(global(&interpreter, "defaults"), numbers(&[3.0, 6.0, 9.0]));
+        assert_eq!(global(&interpreter, "given"), numbers(&[3.0, 1.0, 4.0]));
+        assert_eq!(
+            run_err("func f(a = b, b = 1) { return a }\nf()"),
+            "Undefined variable: b"
+        );
+    }
+
+    #[test]
+    fn test_index_and_compound_assignment() {
+        let interpreter = run(
+            r#"calls = 0
+               func f() {
+                   calls = calls + 1
+                   return 1
+               }
+               arr = [10, 20, 30]
+               arr[f()] += 1
+               arr[0] = arr[2] - 5
+               grid = [[1, 2], [3, 4]]
+               grid[1][0] *= 10
+               counts = {apples: 2}
+               counts["apples"] *= 2
+               counts["pears"] = 1
+               n = 5
+               n -= 2
+               n /= 2
+               letter = "héllo"[1]"#,
+        );
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        assert_eq!(global(&interpreter, "arr"), numbers(&[25.0, 21.0, 30.0]));
+        assert_eq!(global(&interpreter, "calls"), Value::Number(1.0));
+        assert_eq!(
+            global(&interpreter, "grid"),
+            Value::Array(vec![numbers(&[1.0, 2.0]), numbers(&[30.0, 4.0])])
+        );
+        assert_eq!(global(&interpreter, "counts").to_string(), "{apples: 4, pears: 1}");
+        assert_eq!(global(&interpreter, "n"), Value::Number(1.5));
+        assert_eq!(global(&interpreter, "letter"), Value::String("é".to_string()));
+
+        assert_eq!(run_err("a = [1]\na[2] = 2"), "Index 2 out of bounds for length 1");
+        assert_eq!(run_err("a = [1]\na[1] += 2"), "Index 1 out of bounds for length 1");
+        assert_eq!(run_err("a = [1]\nb = a[\"x\"]"), "Array index must be a number, got String");
+    }
+
+    #[test]
+    fn test_index_assignment_at_end_appends() {
+        let interpreter = run(
+            r#"arr = [1, 2]
+               arr[len(arr)] = 3
+               arr[2] = 30
+               empty = []
+               empty[0] = "first"
+               grid = [[1]]
+               grid[0][1] = 2"#,
+        );
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        assert_eq!(global(&interpreter, "arr"), numbers(&[1.0, 2.0, 30.0]));
+        assert_eq!(global(&interpreter, "empty"), Value::Array(vec![Value::String("first".to_string())]));
+        assert_eq!(global(&interpreter, "grid"), Value::Array(vec![numbers(&[1.0, 2.0])]));
+        assert_eq!(run_err("a = [1, 2]\na[5] = 0"), "Index 5 out of bounds for length 2");
+    }
+
+    #[test]
+    fn test_rest_parameters() {
+        let interpreter = run(
+            r#"func sum(...nums) {
+                   total = 0
+                   for (n in nums) {
+                       total += n
+                   }
+                   return total
+               }
+               func label(prefix, ...parts) {
+                   return prefix + ": " + join(parts, ", ")
+               }
+               none = sum()
+               several = sum(1, 2, 3, 4)
+               labelled = label("tags", "a", "b")
+               bare = label("empty")"#,
+        );
+        assert_eq!(global(&interpreter, "none"), Value::Number(0.0));
+        assert_eq!(global(&interpreter, "several"), Value::Number(10.0));
+        assert_eq!(global(&interpreter, "labelled"), Value::String("tags: a, b".to_string()));
+        assert_eq!(global(&interpreter, "bare"), Value::String("empty: ".to_string()));
+        assert_eq!(
+            run_err("func f(a, ...rest) { return a }\nf()"),
+            "Function f expects at least 1 arguments, got 0"
+        );
+    }
+
+    #[test]
+    fn test_let_shadows_outer_variables() {
+        let interpreter = run(
+            r#"x = 1
+               total = 0
+               if (true) {
+                   let x = 10
+                   x += 5
+                   total = x
+               }
+               for (let i = 0; i < 3; i += 1) {
+                   let total = i
+               }
+               outer_i = 7
+               func f() {
+                   let outer_i = 1
+                   return outer_i
+               }
+               inner = f()"#,
+        );
+        assert_eq!(global(&interpreter, "x"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "total"), Value::Number(15.0));
+        assert_eq!(global(&interpreter, "outer_i"), Value::Number(7.0));
+        assert_eq!(global(&interpreter, "inner"), Value::Number(1.0));
+    }
+
+    #[test]
+    fn test_strict_mode_comparisons() {
+        let source = r#"result = "5" < 10"#;
+        assert_eq!(global(&run(source), "result"), Value::Boolean(true));
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_strict(true);
+        let err = interpreter.execute(&parse(source)).unwrap_err().message;
+        assert_eq!(err, "Cannot compare String and Number");
+        let err = interpreter.execute(&parse("true >= 0")).unwrap_err().message;
+        assert_eq!(err, "Cannot compare Boolean and Number");
+        interpreter.execute(&parse(r#"ok = 5 < 10 && "5" < "6" && false < true"#)).unwrap();
+        assert_eq!(interpreter.globals.get("ok"), Some(&Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_anonymous_functions() {
+        let interpreter = run(
+            r#"func make_adder(n) {
+                   return func(x) {
+                       let total = x + n
+                       return total
+                   }
+               }
+               add5 = make_adder(5)
+               added = add5(10)
+               classify = func(x) {
+                   if (x < 0) {
+                       return "negative"
+                   }
+                   return "non-negative"
+               }
+               classes = map([-1, 2], classify)
+               double = func(a, b = 2) { return a * b }
+               doubled = double(21)
+               no_return = func() { x = 1 }
+               nothing = no_return()"#,
+        );
+        assert_eq!(global(&interpreter, "added"), Value::Number(15.0));
+        assert_eq!(
+            global(&interpreter, "classes"),
+            Value::Array(vec![Value::String("negative".to_string()), Value::String("non-negative".to_string())])
+        );
+        assert_eq!(global(&interpreter, "doubled"), Value::Number(42.0));
+        assert_eq!(global(&interpreter, "nothing"), Value::Null);
+    }
+
+    const ACCOUNT: &str = r#"
+        class Account {
+            const id = 0
+            private balance = 0
+            func init(id, amount) {
+                this.id = id
+                balance = amount
+            }
+            func deposit(n) {
+                balance = balance + this.checked(n)
+            }
+            private func checked(n) {
+                if (n < 0) {
+                    return 0
+                }
+                return n
+            }
+            func total() {
+                return balance
+            }
+            func renumber() {
+                id = id + 1
+            }
+        }
+        account = new Account(7, 10)
+        account.deposit(5)
+        account.deposit(-3)
+    "#;
+
+    #[test]
+    fn test_const_and_private_members() {
+        let interpreter = run(&format!("{}\nid = account.id\ntotal = account.total()", ACCOUNT));
+        assert_eq!(global(&interpreter, "id"), Value::Number(7.0));
+        assert_eq!(global(&interpreter, "total"), Value::Number(15.0));
+    }
+
+    #[test]
+    fn test_const_property_reassignment() {
+        assert_eq!(
+            run_err(&format!("{}\naccount.id = 8", ACCOUNT)),
+            "Cannot assign to const property 'id' of class 'Account'"
+        );
+        assert_eq!(
+            run_err(&format!("{}\naccount.renumber()", ACCOUNT)),
+            "Cannot assign to const property 'id' of class 'Account'"
+        );
+    }
+
+    #[test]
+    fn test_private_members_from_outside() {
+        assert_eq!(
+            run_err(&format!("{}\nprint(account.balance)", ACCOUNT)),
+            "Cannot access private property 'balance' of class 'Account' from outside the class"
+        );
+        assert_eq!(
+            run_err(&format!("{}\naccount.balance = 100", ACCOUNT)),
+            "Cannot assign private property 'balance' of class 'Account' from outside the class"
+        );
+        assert_eq!(
+            run_err(&format!("{}\naccount.checked(1)", ACCOUNT)),
+            "Cannot call private method 'checked' of class 'Account' from outside the class"
+        );
+    }
+
+    #[test]
+    fn test_private_members_hidden_from_subclasses() {
+        let savings = format!(
+            "{}\nclass Savings extends Account {{
+                 func peek() {{ return balance }}
+                 func peek_this() {{ return this.balance }}
+                 func check() {{ return this.checked(1) }}
+                 func top_up() {{
+                     this.deposit(5)
+                     return this.total()
+                 }}
+             }}
+             savings = new Savings(1, 100)",
+            ACCOUNT
+        );
+        assert_eq!(run_err(&format!("{}\nsavings.peek()", savings)), "Undefined variable: balance");
+        assert_eq!(
+            run_err(&format!("{}\nsavings.peek_this()", savings)),
+            "Cannot access private property 'balance' of class 'Account' from outside the class"
+        );
+        assert_eq!(
+            run_err(&format!("{}\nsavings.check()", savings)),
+            "Cannot call private method 'checked' of class 'Account' from outside the class"
+        );
+
+        // The declaring class's own methods still see the property on a subclass
+        let interpreter = run(&format!("{}\ntopped = savings.top_up()\nafter = savings.total()", savings));
+        assert_eq!(global(&interpreter, "topped"), Value::Number(105.0));
+        assert_eq!(global(&interpreter, "after"), Value::Number(105.0));
+    }
+
+    #[test]
+    fn test_objects_compare_with_eq_method() {
+        let interpreter = run(
+            r#"class Fraction {
+                   num = 0
+                   den = 1
+                   func init(num, den) {
+                       this.num = num
+                       this.den = den
+                   }
+                   func __eq__(other) {
+                       return num * other.den == other.num * den
+                   }
+               }
+               class Point { x = 0 }
+               half = new Fraction(1, 2)
+               same = half == new Fraction(2, 4)
+               different = half != new Fraction(2, 3)
+               found = index_of([new Fraction(1, 3), new Fraction(3, 6)], half)
+               structural = new Point() == new Point()"#,
+        );
+        assert_eq!(global(&interpreter, "same"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "different"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "found"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "structural"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_hoisted_mutual_recursion() {
+        let interpreter = run(
+            "ten_is_even = isEven(10)
+             func isEven(n) {
+                 if (n == 0) {
+                     return true
+                 }
+                 return isOdd(n - 1)
+             }
+             func isOdd(n) {
+                 if (n == 0) {
+                     return false
+                 }
+                 return isEven(n - 1)
+             }
+             ten_is_odd = isOdd(10)",
+        );
+        assert_eq!(global(&interpreter, "ten_is_even"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "ten_is_odd"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_take_while_and_drop_while() {
+        let interpreter = run(
+            r#"calls = 0
+               func small(x) {
+                   calls = calls + 1
+                   return x < 10
+               }
+               sorted = [1, 4, 9, 16, 25]
+               taken = take_while(sorted, small)
+               calls_after_take = calls
+               dropped = sorted.drop_while((x) => x < 10)
+               all_taken = take_while(sorted, (x) => x < 100)
+               none_dropped = drop_while(sorted, (x) => x > 100)"#,
+        );
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        assert_eq!(global(&interpreter, "taken"), numbers(&[1.0, 4.0, 9.0]));
+        assert_eq!(global(&interpreter, "calls_after_take"), Value::Number(4.0));
+        assert_eq!(global(&interpreter, "dropped"), numbers(&[16.0, 25.0]));
+        assert_eq!(global(&interpreter, "all_taken"), numbers(&[1.0, 4.0, 9.0, 16.0, 25.0]));
+        assert_eq!(global(&interpreter, "none_dropped"), numbers(&[1.0, 4.0, 9.0, 16.0, 25.0]));
+        assert_eq!(run_err("x = take_while(5, (x) => true)"), "take_while expects an array, got Number");
+    }
+
+    #[test]
+    fn test_any_and_all() {
+        let interpreter = run(
+            r#"calls = 0
+               func is_big(x) {
+                   calls = calls + 1
+                   return x > 1
+               }
+               items = [1, 2, 3]
+               some_big = any(items, is_big)
+               calls_after_any = calls
+               all_positive = items.all((x) => x > 0)
+               all_big = all(items, (x) => x > 1)
+               none_big = any(items, (x) => x > 10)
+               any_empty = any([], (x) => true)
+               all_empty = all([], (x) => false)"#,
+        );
+        assert_eq!(global(&interpreter, "some_big"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "calls_after_any"), Value::Number(2.0));
+        assert_eq!(global(&interpreter, "all_positive"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "all_big"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "none_big"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "any_empty"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "all_empty"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_return_value_must_start_on_same_line() {
+        let interpreter = run(
+            r#"func bare() {
+                   return
+               }
+               func bare_before_statement() {
+                   calls = 1
+                   return
+                   calls = 2
+               }
+               func single(x) {
+                   return x
+               }
+               func sum(x, y) {
+                   return x + y
+               }
+               func last(x) { return x * 2 }
+               a = bare()
+               b = bare_before_statement()
+               c = single(3)
+               d = sum(3, 4)
+               e = last(5)"#,
+        );
+        assert_eq!(global(&interpreter, "a"), Value::Null);
+        assert_eq!(global(&interpreter, "b"), Value::Null);
+        assert_eq!(global(&interpreter, "c"), Value::Number(3.0));
+        assert_eq!(global(&interpreter, "d"), Value::Number(7.0));
+        assert_eq!(global(&interpreter, "e"), Value::Number(10.0));
+    }
+
+    #[test]
+    fn test_return_inside_callback_returns_from_callback() {
+        let interpreter = run(
+            r#"func label(items) {
+                   labels = items.map(func(n) {
+                       if (n > 1) {
+                           return "big"
+                       }
+                       return "small"
+                   })
+                   kept = filter(items, func(n) {
+                       return n != 2
+                   })
+                   return [labels, kept, "done"]
+               }
+               result = label([1, 2, 3])"#,
+        );
+        let string = |s: &str| Value::String(s.to_string());
+        assert_eq!(
+            global(&interpreter, "result"),
+            Value::Array(vec![
+                Value::Array(vec![string("small"), string("big"), string("big")]),
+                Value::Array(vec![Value::Number(1.0), Value::Number(3.0)]),
+                string("done"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_self_assignment_stores_a_snapshot() {
+        // Objects are values, so `a.next = a` stores a copy of `a` as it was and
+        // printing or serializing never meets a cycle
+        let interpreter = run(
+            r#"class Node {
+                   value = 1
+                   next = null
+               }
+               a = new Node()
+               a.next = a
+               a.next = a
+               text = to_string(a)
+               json = to_json(a)"#,
+        );
+        assert_eq!(global(&interpreter, "text"), Value::String("<Node object>".to_string()));
+        assert_eq!(
+            global(&interpreter, "json"),
+            Value::String(r#"{"next":{"next":{"next":null,"value":1},"value":1},"value":1}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_runtime_error_reports_line() {
+        let mut interpreter = Interpreter::new();
+        let source = "func area(shape) {\n    return shape.width * shape.height\n}\nsize = area(\"square\")\n";
+        let err = interpreter.execute(&parse(source)).unwrap_err();
+        assert_eq!(err.line, Some(2));
+        assert_eq!(err.to_string(), "Error at line 2: Cannot access property 'width' on String");
+    }
+
+    #[test]
+    fn test_new_with_field_initializers() {
+        let interpreter = run(
+            r#"class Point {
+                   x = 0
+                   y = 0
+                   label = "origin"
+                   func init() {
+                       label = "from init"
+                   }
+               }
+               p = new Point { x: 1, y: 2 + 3 }
+               px = p.x
+               py = p.y
+               label = p.label"#,
+        );
+        assert_eq!(global(&interpreter, "px"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "py"), Value::Number(5.0));
+        assert_eq!(global(&interpreter, "label"), Value::String("origin".to_string()));
+        assert_eq!(
+            run_err("class Point { x = 0 }\np = new Point { z: 1 }"),
+            "Class 'Point' has no property 'z'"
+        );
+    }
+
+    #[test]
+    fn test_null_coalescing() {
+        let interpreter = run(
+            r#"calls = 0
+               func fallback() {
+                   calls = calls + 1
+                   return 5
+               }
+               from_null = null ?? 5
+               from_zero = 0 ?? 5
+               from_false = false ?? 5
+               lazy = "set" ?? fallback()
+               chained = null ?? null ?? "last"
+               looser_than_or = false || null ?? 1"#,
+        );
+        assert_eq!(global(&interpreter, "from_null"), Value::Number(5.0));
+        assert_eq!(global(&interpreter, "from_zero"), Value::Number(0.0));
+        assert_eq!(global(&interpreter, "from_false"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "lazy"), Value::String("set".to_string()));
+        assert_eq!(global(&interpreter, "calls"), Value::Number(0.0));
+        assert_eq!(global(&interpreter, "chained"), Value::String("last".to_string()));
+        assert_eq!(global(&interpreter, "looser_than_or"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn test_lazy_thunk_runs_once() {
+        let interpreter = run(
+            r#"runs = 0
+               func expensive() {
+                   runs = runs + 1
+                   return 42
+               }
+               thunk = lazy(() => expensive())
+               before = runs
+               copy = thunk
+               first = force(thunk)
+               second = force(copy)
+               third = force(thunk)
+               kind = typeof(thunk)"#,
+        );
+        assert_eq!(global(&interpreter, "before"), Value::Number(0.0));
+        assert_eq!(global(&interpreter, "first"), Value::Number(42.0));
+        assert_eq!(global(&interpreter, "second"), Value::Number(42.0));
+        assert_eq!(global(&interpreter, "third"), Value::Number(42.0));
+        assert_eq!(global(&interpreter, "runs"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "kind"), Value::String("Thunk".to_string()));
+    }
+
+    #[test]
+    fn test_zip_with() {
+        let interpreter = run(
+            r#"sums = zip_with([1, 2, 3], [10, 20, 30, 40], (x, y) => x + y)
+               labels = ["a", "b"].zip_with([1, 2], (name, n) => name + to_string(n))"#,
+        );
+        assert_eq!(
+            global(&interpreter, "sums"),
+            Value::Array(vec![Value::Number(11.0), Value::Number(22.0), Value::Number(33.0)])
+        );
+        assert_eq!(
+            global(&interpreter, "labels"),
+            Value::Array(vec![Value::String("a1".to_string()), Value::String("b2".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_optional_chaining() {
+        let interpreter = run(
+            r#"class Address {
+                   city = "Lyon"
+                   func label() {
+                       return "in " + city
+                   }
+               }
+               class User {
+                   address = null
+               }
+               calls = 0
+               func count() {
+                   calls = calls + 1
+                   return 1
+               }
+               missing = null?.x
+               user = new User()
+               no_city = user.address?.city
+               no_label = user.address?.label(count())
+               user.address = new Address()
+               city = user?.address?.city
+               label = user.address?.label()"#,
+        );
+        assert_eq!(global(&interpreter, "missing"), Value::Null);
+        assert_eq!(global(&interpreter, "no_city"), Value::Null);
+        assert_eq!(global(&interpreter, "no_label"), Value::Null);
+        assert_eq!(global(&interpreter, "calls"), Value::Number(0.0));
+        assert_eq!(global(&interpreter, "city"), Value::String("Lyon".to_string()));
+        assert_eq!(global(&interpreter, "label"), Value::String("in Lyon".to_string()));
+        assert_eq!(run_err("a = null\nb = a.x"), "Cannot access property 'x' on Null");
+
+        // The first `?.` that finds null ends the whole chain
+        let interpreter = run(
+            r#"x = null
+               deep = x?.a.b
+               called = x?.m().b
+               indexed = x?.items[0].name
+               literal = null?.a.b"#,
+        );
+        for name in ["deep", "called", "indexed", "literal"] {
+            assert_eq!(global(&interpreter, name), Value::Null, "{}", name);
+        }
+        // A null found past the `?.` is still an error
+        assert_eq!(
+            run_err("class Box { inner = null }\nb = new Box()\nc = b?.inner.x"),
+            "Cannot access property 'x' on Null"
+        );
+    }
+
+    #[test]
+    fn test_array_spread() {
+        let interpreter = run(
+            r#"rest = [2, 3]
+               none = []
+               leading = [...rest, 4]
+               middle = [1, ...rest, 4]
+               trailing = [1, ...rest]
+               empty = [1, ...none, 2]
+               nested = [...[...rest, ...rest]]"#,
+        );
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        assert_eq!(global(&interpreter, "leading"), numbers(&[2.0, 3.0, 4.0]));
+        assert_eq!(global(&interpreter, "middle"), numbers(&[1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(global(&interpreter, "trailing"), numbers(&[1.0, 2.0, 3.0]));
+        assert_eq!(global(&interpreter, "empty"), numbers(&[1.0, 2.0]));
+        assert_eq!(global(&interpreter, "nested"), numbers(&[2.0, 3.0, 2.0, 3.0]));
+        assert_eq!(run_err("x = [...5]"), "Cannot spread Number into an array");
+    }
+
+    #[test]
+    fn test_seeded_random_is_reproducible() {
+        let interpreter = run(
+            r#"seed(42)
+               first = [random(), random(), random_int(1, 6)]
+               seed(42)
+               second = [random(), random(), random_int(1, 6)]
+               in_range = true
+               repeat (200) {
+                   n = random_int(-2, 2)
+                   r = random()
+                   in_range = in_range && n >= -2 && n <= 2 && r >= 0 && r < 1
+               }
+               same = random_int(3, 3)"#,
+        );
+        assert_eq!(global(&interpreter, "first"), global(&interpreter, "second"));
+        assert_eq!(global(&interpreter, "in_range"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "same"), Value::Number(3.0));
+        assert_eq!(run_err("x = random_int(5, 1)"), "random_int expects lo <= hi, got 5 and 1");
+        assert_eq!(
+            run_err("x = random_int(0, 1e20)"),
+            "random_int expects bounds within 2^53 of zero and less than 2^53 apart, got 0 and 100000000000000000000"
+        );
+        assert_eq!(
+            run_err("x = random_int(-2 ** 52 - 1, 2 ** 52)"),
+            "random_int expects bounds within 2^53 of zero and less than 2^53 apart, got -4503599627370497 and 4503599627370496"
+        );
+    }
+
+    #[test]
+    fn test_sort_by_key() {
+        let interpreter = run(
+            r#"class Person {
+                   name = ""
+                   age = 0
+               }
+               people = [new Person { name: "Cy", age: 40 }, new Person { name: "Ann", age: 25 }, new Person { name: "Bo", age: 25 }]
+               by_age = map(sort_by_key(people, "age"), (p) => p.name)
+               by_name = map(people.sort_by_key("name"), (p) => p.name)"#,
+        );
+        let strings = |items: &[&str]| Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect());
+        // Equal ages keep their original order
+        assert_eq!(global(&interpreter, "by_age"), strings(&["Ann", "Bo", "Cy"]));
+        assert_eq!(global(&interpreter, "by_name"), strings(&["Ann", "Bo", "Cy"]));
+
+        let class = "class P { age = 1 }\nclass Q { }\n";
+        assert_eq!(
+            run_err(&format!("{}x = sort_by_key([new P(), new Q()], \"age\")", class)),
+            "Property 'age' not found on object"
+        );
+        assert_eq!(
+            run_err(&format!("{}x = sort_by_key([new P(), 5], \"age\")", class)),
+            "sort_by_key expects an array of objects, got Number"
+        );
+        assert_eq!(
+            run_err(&format!("{}x = sort_by_key([new P(), new P {{ age: [] }}], \"age\")", class)),
+            "Cannot convert Array to number"
+        );
+        // Method calls are held to the builtin's arity, the array counting as the first argument
+        assert_eq!(run_err("x = [1, 2].sort_by_key()"), "Native function sort_by_key expects 2 arguments, got 1");
+        assert_eq!(run_err("x = [1, 2].push()"), "Native function push expects 2 arguments, got 1");
+    }
+
+    #[test]
+    fn test_print_depth_limits_format_value() {
+        let mut interpreter = run("print_depth(1)");
+        let nested = Value::Array(vec![Value::Array(vec![Value::Number(1.0)])]);
+        assert_eq!(interpreter.format_value(&nested), "[[...]]");
+        interpreter.execute(&parse("print_depth(null)")).unwrap();
+        assert_eq!(interpreter.format_value(&nested), "[[1]]");
+        assert_eq!(run_err("print_depth(-1)"), "print_depth expects a non-negative integer or null, got -1");
+    }
+
+    #[test]
+    fn test_register_native() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register_native(
+            "double",
+            1,
+            Box::new(|args| match &args[0] {
+                Value::Number(n) => Ok(Value::Number(n * 2.0)),
+                other => Err(format!("double expects a Number, got {}", other.type_name())),
+            }),
+        );
+        interpreter.execute(&parse("x = double(21)\ny = map([1, 2], double)")).unwrap();
+        assert_eq!(global(&interpreter, "x"), Value::Number(42.0));
+        assert_eq!(global(&interpreter, "y"), Value::Array(vec![Value::Number(2.0), Value::Number(4.0)]));
+
+        let err = interpreter.execute(&parse("double(\"a\")")).unwrap_err();
+        assert_eq!(err.message, "double expects a Number, got String");
+        let err = interpreter.execute(&parse("double(1, 2)")).unwrap_err();
+        assert_eq!(err.message, "Native function double expects 1 arguments, got 2");
+    }
+
+    #[test]
+    fn test_undefined_is_null_option() {
+        assert_eq!(run_err("x = missing"), "Undefined variable: missing");
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_undefined_is_null(true);
+        interpreter
+            .execute(&parse("x = missing\ntitle = heading ?? \"Untitled\"\ndefined = 1\ny = defined"))
+            .unwrap();
+        assert_eq!(global(&interpreter, "x"), Value::Null);
+        assert_eq!(global(&interpreter, "title"), Value::String("Untitled".to_string()));
+        assert_eq!(global(&interpreter, "y"), Value::Number(1.0));
+        assert!(interpreter.execute(&parse("missing_function()")).is_err());
+    }
+
+    #[test]
+    fn test_foreach_with_index() {
+        let interpreter = run(
+            r#"indices = []
+               items = []
+               for (i, item in ["a", "b", "c"]) {
+                   indices = push(indices, i)
+                   items = push(items, item)
+               }
+               plain = []
+               for (item in [1, 2]) {
+                   plain = push(plain, item)
+               }"#,
+        );
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        let strings = |items: &[&str]| Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect());
+        assert_eq!(global(&interpreter, "indices"), numbers(&[0.0, 1.0, 2.0]));
+        assert_eq!(global(&interpreter, "items"), strings(&["a", "b", "c"]));
+        assert_eq!(global(&interpreter, "plain"), numbers(&[1.0, 2.0]));
+    }
+
+    #[test]
+    fn test_array_destructuring() {
+        let interpreter = run(
+            r#"[x, y] = [1, 2]
+               [head, ...tail] = [1, 2, 3]
+               [only, ...none] = [7]
+               [k, v] = entries({ a: 1 })[0]
+               [1, 2].join(",")"#,
+        );
+        let numbers = |items: &[f64]| Value::Array(items.iter().map(|n| Value::Number(*n)).collect());
+        assert_eq!(global(&interpreter, "x"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "y"), Value::Number(2.0));
+        assert_eq!(global(&interpreter, "head"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "tail"), numbers(&[2.0, 3.0]));
+        assert_eq!(global(&interpreter, "only"), Value::Number(7.0));
+        assert_eq!(global(&interpreter, "none"), numbers(&[]));
+        assert_eq!(global(&interpreter, "k"), Value::String("a".to_string()));
+        assert_eq!(global(&interpreter, "v"), Value::Number(1.0));
+        assert_eq!(
+            run_err("[a, b] = [1, 2, 3]"),
+            "Cannot destructure an array of length 3 into 2 names"
+        );
+        assert_eq!(
+            run_err("[a, b, ...c] = [1]"),
+            "Cannot destructure an array of length 1 into at least 2 names"
+        );
+        assert_eq!(run_err("[a] = 5"), "Cannot destructure Number, expected an Array");
+    }
+
+    #[test]
+    fn test_truthiness_is_consistent() {
+        let cases = [
+            ("null", false),
+            ("false", false),
+            ("0", false),
+            ("\"\"", false),
+            ("[]", false),
+            ("true", true),
+            ("1", true),
+            ("-0.5", true),
+            ("\"0\"", true),
+            ("\"false\"", true),
+            ("[0]", true),
+            ("{}", true),
+            ("(x) => x", true),
+            ("print", true),
+            ("Empty", true),
+            ("new Empty()", true),
+        ];
+        for (value, expected) in cases {
+            let interpreter = run(&format!(
+                r#"class Empty {{ }}
+                   v = {value}
+                   via_if = false
+                   if (v) {{
+                       via_if = true
+                   }}
+                   via_while = false
+                   while (v && !via_while) {{
+                       via_while = true
+                   }}
+                   via_not = !!v
+                   via_or = v || false
+                   via_guard = match (1) {{
+                       case _ if v => true
+                       case _ => false
+                   }}
+                   via_filter = len(filter([1], (x) => v)) == 1"#
+            ));
+            for name in ["via_if", "via_while", "via_not", "via_or", "via_guard", "via_filter"] {
+                assert_eq!(global(&interpreter, name), Value::Boolean(expected), "{} for {}", name, value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() {
+        let interpreter = run(
+            r#"calls = 0
+               func touch() {
+                   calls = calls + 1
+                   return true
+               }
+               user = null
+               safe = user != null && user.name == "ann"
+               a = false && touch()
+               b = true || touch()
+               c = true && touch()
+               d = false || touch()"#,
+        );
+        assert_eq!(global(&interpreter, "safe"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "a"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "b"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "c"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "d"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "calls"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_methods_and_properties_reflection() {
+        let interpreter = run(
+            r#"class Animal {
+                   name = "animal"
+                   func speak() {
+                       return "..."
+                   }
+                   func describe() {
+                       return name
+                   }
+               }
+               class Dog extends Animal {
+                   breed = "mutt"
+                   override func speak() {
+                       return "woof"
+                   }
+                   func fetch() {
+                       return this.secret()
+                   }
+                   private func secret() {
+                       return "stick"
+                   }
+               }
+               dog = new Dog()
+               method_names = methods(dog)
+               property_names = properties(dog)"#,
+        );
+        let strings = |items: &[&str]| Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect());
+        assert_eq!(global(&interpreter, "method_names"), strings(&["describe", "fetch", "speak"]));
+        assert_eq!(global(&interpreter, "property_names"), strings(&["breed", "name"]));
+        assert_eq!(run_err("x = properties({a: 1})"), "properties expects an Object, got Map");
+    }
+
+    #[test]
+    fn test_input_reads_lines() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input(Box::new(io::Cursor::new("Ada\r\n42\nlast")));
+        interpreter
+            .execute(&parse("name = input()\nage = num(input(\"\"))\nrest = input()\neof = input()"))
+            .unwrap();
+        assert_eq!(global(&interpreter, "name"), Value::String("Ada".to_string()));
+        assert_eq!(global(&interpreter, "age"), Value::Number(42.0));
+        assert_eq!(global(&interpreter, "rest"), Value::String("last".to_string()));
+        assert_eq!(global(&interpreter, "eof"), Value::Null);
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
@@ -7,8 +9,9 @@ pub enum Value {
     String(String),
     Boolean(bool),
     Array(Vec<Value>),
+    Map(HashMap<String, Value>),
     Function {
-        params: Vec<String>,
+        params: Vec<crate::parser::ast::Param>,
         body: Vec<crate::parser::ast::Stmt>,
         closure: HashMap<String, Value>,
     },
@@ -19,21 +22,60 @@ pub enum Value {
     },
     NativeFunction {
         name: String,
-        arity: usize,
+        arity: Arity,
     },
     Class {
         name: String,
         parent: Option<Box<Value>>,
-        methods: HashMap<String, (Vec<String>, Vec<crate::parser::ast::Stmt>)>, // method_name -> (params, body)
+        methods: HashMap<String, (Vec<crate::parser::ast::Param>, Vec<crate::parser::ast::Stmt>)>, // method_name -> (params, body)
         properties: HashMap<String, Value>, // default properties
+        private: HashSet<String>,   // members declared `private`
+        constants: HashSet<String>, // properties declared `const`
     },
     Object {
         class_name: String,
         properties: HashMap<String, Value>,
     },
+    Thunk {
+        func: Box<Value>,                  // zero-argument function computing the value
+        memo: Rc<RefCell<Option<Value>>>, // result of the first `force`, shared by every copy
+    },
     Null,
 }
 
+/// Number of arguments a native function accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    Range(usize, usize), // inclusive
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(n) => count == *n,
+            Arity::Range(min, max) => count >= *min && count <= *max,
+            Arity::AtLeast(min) => count >= *min,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+            Arity::AtLeast(min) => write!(f, "at least {}", min),
+        }
+    }
+}
+
+/// Names reported by `Value::type_name`, usable as type patterns in `match`
+pub const TYPE_NAMES: &[&str] = &[
+    "Number", "String", "Boolean", "Array", "Map", "Function", "Class", "Object", "Thunk", "Null",
+];
+
 impl Value {
     pub fn type_name(&self) -> &str {
         match self {
@@ -41,15 +83,96 @@ impl Value {
             Value::String(_) => "String",
             Value::Boolean(_) => "Boolean",
             Value::Array(_) => "Array",
+            Value::Map(_) => "Map",
             Value::Function { .. } => "Function",
             Value::Lambda { .. } => "Function",
             Value::NativeFunction { .. } => "Function",
             Value::Class { .. } => "Class",
             Value::Object { class_name: _, .. } => "Object",
+            Value::Thunk { .. } => "Thunk",
             Value::Null => "Null",
         }
     }
 
+    /// Look up a method on a class, searching parent classes when it isn't defined directly
+    pub fn find_method(&self, name: &str) -> Option<&(Vec<crate::parser::ast::Param>, Vec<crate::parser::ast::Stmt>)> {
+        match self.method_owner(name)? {
+            Value::Class { methods, .. } => methods.get(name),
+            _ => None,
+        }
+    }
+
+    /// Default properties for a new instance: inherited ones first, overridden by
+    /// the class's own
+    pub fn default_properties(&self) -> HashMap<String, Value> {
+        match self {
+            Value::Class { parent, properties, .. } => {
+                let mut result = parent.as_ref().map(|p| p.default_properties()).unwrap_or_default();
+                for (name, val) in properties {
+                    result.insert(name.clone(), val.clone());
+                }
+                result
+            }
+            _ => HashMap::new(),
+        }
+    }
+
+    /// Whether this class is `name` or extends it, directly or through its ancestors
+    pub fn extends(&self, name: &str) -> bool {
+        match self {
+            Value::Class { name: class_name, parent, .. } => {
+                class_name == name || parent.as_ref().is_some_and(|p| p.extends(name))
+            }
+            _ => false,
+        }
+    }
+
+    /// The class that defines a method: this one, or the nearest parent class that does
+    pub fn method_owner(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Class { methods, .. } if methods.contains_key(name) => Some(self),
+            Value::Class { parent, .. } => parent.as_ref().and_then(|p| p.method_owner(name)),
+            _ => None,
+        }
+    }
+
+    /// Names of the methods a class defines or inherits, sorted
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut class = Some(self);
+        while let Some(Value::Class { methods, parent, .. }) = class {
+            names.extend(methods.keys().cloned());
+            class = parent.as_deref();
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The class that declares a member `private`: this one, or the nearest parent
+    /// class that does
+    pub fn private_owner(&self, member: &str) -> Option<&str> {
+        match self {
+            Value::Class { name, private, .. } if private.contains(member) => Some(name),
+            Value::Class { parent, .. } => parent.as_ref().and_then(|p| p.private_owner(member)),
+            _ => None,
+        }
+    }
+
+    /// Whether this class or one of its parents declares a property `const`
+    pub fn is_const(&self, property: &str) -> bool {
+        match self {
+            Value::Class { constants, parent, .. } => {
+                constants.contains(property) || parent.as_ref().is_some_and(|p| p.is_const(property))
+            }
+            _ => false,
+        }
+    }
+
+    /// The truthiness contract shared by `if`, loops, match guards, `!`, `&&`,
+    /// `||`, and predicate callbacks: `null`, `false`, `0`, the empty string, and
+    /// the empty array are falsy; everything else, including maps, functions,
+    /// classes, and objects, is truthy
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Null => false,
@@ -71,9 +194,25 @@ impl Value {
     }
 }
 
-impl fmt::Display for Value {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Value {
+    /// Like `to_string`, but arrays and maps nested more than `max_depth` levels
+    /// deep are shown as `[...]` and `{...}`
+    pub fn to_string_with_depth(&self, max_depth: usize) -> String {
+        struct Limited<'a>(&'a Value, usize);
+        impl fmt::Display for Limited<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.write(f, Some(self.1))
+            }
+        }
+        Limited(self, max_depth).to_string()
+    }
+
+    /// Shared by `Display` (no depth limit) and `to_string_with_depth`
+    fn write(&self, f: &mut fmt::Formatter, depth: Option<usize>) -> fmt::Result {
+        let inner = depth.map(|d| d.saturating_sub(1));
         match self {
+            Value::Array(_) if depth == Some(0) => write!(f, "[...]"),
+            Value::Map(_) if depth == Some(0) => write!(f, "{{...}}"),
             Value::Number(n) => {
                 if n.fract() == 0.0 {
                     write!(f, "{}", *n as i64)
@@ -89,16 +228,39 @@ impl fmt::Display for Value {
                     if i > 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "{}", v)?;
+                    v.write(f, inner)?;
                 }
                 write!(f, "]")
             }
+            Value::Map(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                write!(f, "{{")?;
+                for (i, key) in keys.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", key)?;
+                    map[*key].write(f, inner)?;
+                }
+                write!(f, "}}")
+            }
             Value::Function { params, .. } => write!(f, "<function({})>", params.len()),
             Value::Lambda { params, .. } => write!(f, "<lambda({})>", params.len()),
             Value::NativeFunction { name, arity } => write!(f, "<native function {}({})>", name, arity),
             Value::Class { name, .. } => write!(f, "<class {}>", name),
             Value::Object { class_name, .. } => write!(f, "<{} object>", class_name),
+            Value::Thunk { memo, .. } => match &*memo.borrow() {
+                Some(value) => write!(f, "<thunk {}>", value),
+                None => write!(f, "<thunk>"),
+            },
             Value::Null => write!(f, "null"),
         }
     }
 }
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, None)
+    }
+}
@@ -0,0 +1,54 @@
+use platypus::{Interpreter, Lexer, Parser, Value};
+
+#[test]
+fn test_pipeline_through_public_api() {
+    let source = "func fib(n) {
+                      if (n < 2) { return n }
+                      return fib(n - 1) + fib(n - 2)
+                  }
+                  result = map([5, 10], fib)";
+    let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+    let program = Parser::new(tokens).parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.execute(&program).unwrap();
+    assert_eq!(
+        interpreter.eval("result").unwrap(),
+        Value::Array(vec![Value::Number(5.0), Value::Number(55.0)])
+    );
+}
+
+#[test]
+fn test_errors_through_public_api() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_global("limit", Value::Number(3.0));
+
+    let err = interpreter.run("x = 1\ny = limit + missing").unwrap_err();
+    assert_eq!(err.line, Some(2));
+    assert_eq!(err.to_string(), "Error at line 2: Undefined variable: missing");
+    assert_eq!(interpreter.eval("x").unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn test_failed_call_leaves_no_state_behind() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .run(
+            "class A {
+                 private secret = 42
+                 func boom() { return missing }
+             }
+             a = new A()
+             func f(inner) { return missing }",
+        )
+        .unwrap();
+
+    interpreter.eval("f(1)").unwrap_err();
+    assert_eq!(interpreter.eval("inner").unwrap_err().message, "Undefined variable: inner");
+
+    let refused = interpreter.eval("a.secret").unwrap_err().message;
+    interpreter.eval("a.boom()").unwrap_err();
+    assert_eq!(interpreter.eval("a.secret").unwrap_err().message, refused);
+    interpreter.run("a.boom()").unwrap_err();
+    assert_eq!(interpreter.eval("a.secret").unwrap_err().message, refused);
+}
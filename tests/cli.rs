@@ -0,0 +1,227 @@
+use std::fs;
+use std::path::PathBuf;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn write_script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("platypus_cli_{}_{}.plat", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn platypus(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_platypus"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_trace_logs_statements_in_order() {
+    let script = write_script("trace", "x = 1\nprint(x)\nif (x > 0) {\n    y = 2\n}\n");
+    let output = platypus(&["run", "--trace", script.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "[trace] line 1: VarDecl",
+            "[trace] line 2: Expr",
+            "[trace] line 3: If",
+            "[trace] line 3: Block",
+            "[trace] line 4: VarDecl",
+        ]
+    );
+}
+
+#[test]
+fn test_run_without_trace_is_silent() {
+    let script = write_script("no_trace", "x = 1\n");
+    let output = platypus(&["run", script.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_print_uses_to_string_method() {
+    let source = "class Point {\n    x = 1\n    func toString() {\n        return \"Point(\" + to_string(x) + \")\"\n    }\n}\nclass Plain {\n}\nprint(new Point())\nprint(new Plain())\n";
+    let script = write_script("to_string", source);
+    let output = platypus(&["run", script.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Point(1)\n<Plain object>\n");
+}
+
+#[test]
+fn test_for_each_calls_function_per_element() {
+    let script = write_script("for_each", "result = forEach([1, 2, 3], (x) => print(x))\nprint(result)\n");
+    let output = platypus(&["run", script.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n2\n3\nnull\n");
+}
+
+#[test]
+fn test_profile_reports_call_count() {
+    let source = "func square(x) {\n    return x * x\n}\ntotal = 0\nfor (i = 0; i < 3; i = i + 1) {\n    total = total + square(i)\n}\nprint(total)\n";
+    let script = write_script("profile", source);
+    let output = platypus(&["run", "--profile", script.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "5\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // Three calls to square plus the call to print
+    assert!(stderr.lines().any(|line| line == "[profile] function calls: 4"), "{}", stderr);
+}
+
+#[test]
+fn test_ast_dumps_program_without_running() {
+    let script = write_script("ast", "func double(n) {\n    return n * 2\n}\nprint(double(21))\n");
+    let output = platypus(&["run", "--ast", script.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for node in ["Program", "FuncDecl", "Return", "BinaryOp", "Multiply", "Call"] {
+        assert!(stdout.contains(node), "missing {} in:\n{}", node, stdout);
+    }
+    // The program is not executed, so `print` never outputs 42
+    assert!(!stdout.lines().any(|line| line == "42"));
+}
+
+#[test]
+fn test_tokens_lists_lexer_output() {
+    let script = write_script("tokens", "func f() {\n}\n");
+    let output = platypus(&["run", "--tokens", script.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "1:1 Func",
+            "1:6 Identifier(\"f\")",
+            "1:7 LeftParen",
+            "1:8 RightParen",
+            "1:10 LeftBrace",
+            "2:1 RightBrace",
+            "3:1 Eof",
+        ]
+    );
+}
+
+#[test]
+fn test_eval_runs_inline_code() {
+    let output = platypus(&["-e", "print(1+2)"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+
+    let output = platypus(&["--eval", "x = 1\ny = x.z"]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Error at line 2: Cannot access property 'z' on Number\n");
+}
+
+/// Run a REPL session with the given lines as its input
+fn repl(input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_platypus"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_repl_reads_multi_line_entries() {
+    let output = repl("func double(n) {\n    return n * 2\n}\ndouble(\n  21\n)\nexit\n");
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Each continuation line gets a `.. ` prompt, and the call's result is printed once complete
+    assert!(stdout.contains(">> .. .. >> .. .. 42\n>> Goodbye!"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_meta_commands() {
+    let script = write_script("repl_load", "loaded = \"yes\"\n");
+    let input = format!(
+        "x = 42\nname = \"Ann\"\n:vars\n:clear\n:vars\n:load {}\n:vars\n:nope\nexit\n",
+        script.to_str().unwrap()
+    );
+    let output = repl(&input);
+    fs::remove_file(&script).unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().map(|line| line.trim_start_matches(">> ")).collect();
+    // The first `:vars` lists both variables; after `:clear` there are none until the file defines one
+    assert!(
+        lines.windows(3).any(|w| w == ["name = Ann", "x = 42", "loaded = yes"]),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: Unknown command ':nope', type :help for a list\n"
+    );
+}
+
+#[test]
+fn test_repl_underscore_holds_last_result() {
+    let output = repl("1 + 1\nx = 3\nprint(\"hi\")\n_ * 5\nexit\n");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().map(|line| line.trim_start_matches(">> ")).collect();
+    assert_eq!(&lines[3..6], ["2", "hi", "10"], "{}", stdout);
+}
+
+#[test]
+fn test_print_accepts_any_number_of_arguments() {
+    let output = platypus(&["-e", "print(\"a\", 1, true)\nprint()\nprintln(\"x =\", [1, 2])"]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a 1 true\n\nx = [1, 2]\n");
+}
+
+#[test]
+fn test_check_reports_undefined_variables_before_running() {
+    let script = write_script("check", "print(\"start\")\nif (false) {\n    print(missing)\n}\nprint(later)\nlater = 1\n");
+    let output = platypus(&["run", "--check", script.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: Undefined variable: missing at line 3\nError: Variable 'later' used before its declaration at line 5\n"
+    );
+}
+
+#[test]
+fn test_optimize_folds_constants() {
+    let script = write_script("optimize", "seconds = 2 * 60 * 60\nprint(seconds)\n");
+    let ast = platypus(&["run", "--optimize", "--ast", script.to_str().unwrap()]);
+    let run = platypus(&["run", "--optimize", script.to_str().unwrap()]);
+    fs::remove_file(&script).unwrap();
+
+    let stdout = String::from_utf8_lossy(&ast.stdout);
+    assert!(stdout.contains("7200.0"), "not folded:\n{}", stdout);
+    assert!(!stdout.contains("Multiply"), "not folded:\n{}", stdout);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "7200\n");
+}